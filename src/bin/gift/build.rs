@@ -0,0 +1,24 @@
+#[cfg(feature = "serde")]
+use gift::GIFDescription;
+
+/// Encodes a GIF out of a JSON description.
+#[cfg(feature = "serde")]
+pub fn build(description_path: &str, output_path: &str) -> Result<(), String> {
+    let json = crate::read_file(description_path)?;
+    let description: GIFDescription = serde_json::from_slice(&json)
+        .map_err(|err| format!("cannot parse {}: {}", description_path, err))?;
+    let encoder = description
+        .encode()
+        .map_err(|err| format!("cannot encode {}: {}", description_path, err))?;
+
+    let mut gif_data = Vec::new();
+    encoder
+        .write(&mut gif_data)
+        .and_then(|_| std::fs::write(output_path, gif_data))
+        .map_err(|err| format!("cannot write {}: {}", output_path, err))
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn build(_: &str, _: &str) -> Result<(), String> {
+    Err("building from JSON requires building gift with the \"serde\" feature".to_string())
+}
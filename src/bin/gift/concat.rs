@@ -0,0 +1,22 @@
+use gift::concat_gifs;
+
+/// Writes an animation playing the given GIFs one after the other.
+pub fn concat(paths: &[&str], output_path: &str) -> Result<(), String> {
+    let gif_data = paths
+        .iter()
+        .map(|path| super::read_file(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let gifs = paths
+        .iter()
+        .zip(&gif_data)
+        .map(|(path, gif_data)| super::parse_complete(path, gif_data))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut concatenated_data = Vec::new();
+    concat_gifs(&gifs)
+        .map_err(|err| format!("cannot concatenate GIFs: {}", err))?
+        .write(&mut concatenated_data)
+        .map_err(|err| format!("cannot encode {}: {}", output_path, err))?;
+    std::fs::write(output_path, &concatenated_data)
+        .map_err(|err| format!("cannot write {}: {}", output_path, err))
+}
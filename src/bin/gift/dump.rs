@@ -0,0 +1,362 @@
+use gift::{
+    trace_lzw, Block, DisposalMethod, GraphicControlExtension, ImageData, ImageDescriptor, LZWCode,
+    ParsingError, SubBlocks, GIF,
+};
+
+const BYTES_PER_LINE: usize = 16;
+// LZW codes shown for each sub-block of image data, the rest being counted
+const CODES_PER_SUBBLOCK: usize = 8;
+
+/// Prints consecutive fields of the raw data along with their offsets, bytes and meanings.
+struct Dump<'a> {
+    gif_data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Dump<'a> {
+    fn field<S: AsRef<str>>(&mut self, len: usize, meaning: S) {
+        let bytes = &self.gif_data[self.offset..self.offset + len];
+        let mut meaning = Some(meaning.as_ref());
+
+        for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let line = format!(
+                "{:08x}  {:<width$}  {}",
+                self.offset + i * BYTES_PER_LINE,
+                hex.join(" "),
+                meaning.take().unwrap_or(""),
+                width = BYTES_PER_LINE * 3 - 1
+            );
+            println!("{}", line.trim_end());
+        }
+
+        self.offset += len;
+    }
+
+    fn section(&self, name: &str) {
+        println!();
+        println!("{:08x}  {}", self.offset, name);
+    }
+
+    /// Prints what the preceding fields mean together, without bytes of its own.
+    fn note(&self, meaning: &str) {
+        println!(
+            "{:08x}  {:<width$}  {}",
+            self.offset,
+            "",
+            meaning,
+            width = BYTES_PER_LINE * 3 - 1
+        );
+    }
+
+    fn color_table(&mut self, color_table: &[u8]) {
+        for (index, color) in color_table.chunks(3).enumerate() {
+            self.field(
+                3,
+                format!(
+                    "color {}: #{:02x}{:02x}{:02x}",
+                    index, color[0], color[1], color[2]
+                ),
+            );
+        }
+    }
+
+    fn subblocks(&mut self, subblocks: SubBlocks<'_>, describe: impl Fn(usize, &[u8]) -> String) {
+        for (index, subblock) in subblocks.slices().enumerate() {
+            self.field(1, format!("sub-block size: {}", subblock.len()));
+            self.field(subblock.len(), describe(index, subblock));
+        }
+        self.field(1, "block terminator");
+    }
+
+    fn graphic_control_extension(&mut self, graphic_control_extension: &GraphicControlExtension) {
+        self.section("Graphic Control Extension");
+        self.field(1, "extension introducer");
+        self.field(1, "graphic control label");
+        self.field(
+            1,
+            format!("block size: {}", graphic_control_extension.byte_size),
+        );
+        self.field(
+            1,
+            format!(
+                "disposal method: {}, user input: {}, transparent color: {}",
+                match graphic_control_extension.disposal_method() {
+                    DisposalMethod::Unspecified => "unspecified".to_string(),
+                    DisposalMethod::DoNotDispose => "do not dispose".to_string(),
+                    DisposalMethod::RestoreToBackgroundColor =>
+                        "restore to background color".to_string(),
+                    DisposalMethod::RestoreToPrevious => "restore to previous".to_string(),
+                    DisposalMethod::Reserved(value) => format!("reserved {}", value),
+                },
+                yes_no(graphic_control_extension.packed_field & 0b_0000_0010 != 0),
+                yes_no(graphic_control_extension.transparent_color_flag()),
+            ),
+        );
+        self.field(
+            2,
+            format!(
+                "delay time: {} (1/100 s)",
+                graphic_control_extension.delay_time
+            ),
+        );
+        self.field(
+            1,
+            format!(
+                "transparent color index: {}",
+                graphic_control_extension.transparent_color_index
+            ),
+        );
+        self.field(1, "block terminator");
+    }
+
+    fn image_descriptor(&mut self, image_descriptor: &ImageDescriptor) {
+        self.section("Image Descriptor");
+        self.field(1, "image separator");
+        self.field(2, format!("image left position: {}", image_descriptor.left));
+        self.field(2, format!("image top position: {}", image_descriptor.top));
+        self.field(2, format!("image width: {}", image_descriptor.width));
+        self.field(2, format!("image height: {}", image_descriptor.height));
+        self.field(
+            1,
+            format!(
+                "local color table: {}, interlaced: {}, sorted: {}, size: {} colors",
+                yes_no(image_descriptor.local_color_table_flag()),
+                yes_no(image_descriptor.interlace_flag()),
+                yes_no(image_descriptor.packed_field & 0b_0010_0000 != 0),
+                2 << (image_descriptor.packed_field & 0b_0000_0111)
+            ),
+        );
+    }
+
+    fn extension(&mut self, name: &str, label: &str, data: SubBlocks<'_>) {
+        self.section(name);
+        self.field(1, "extension introducer");
+        self.field(1, label);
+        self.subblocks(data, |_, subblock| text(subblock));
+    }
+
+    /// Shows the LZW codes starting in each sub-block, and how many pixels they decode to.
+    fn image_data(&mut self, image_descriptor: &ImageDescriptor, image_data: &ImageData<'_>) {
+        let pixels = usize::from(image_descriptor.width) * usize::from(image_descriptor.height);
+        let (codes, decoded) = trace_lzw(image_data.lzw_minimum_code_size, image_data.data, pixels);
+        let clear_code = 1 << image_data.lzw_minimum_code_size;
+
+        let mut codes = codes.iter().scan(0, |bit, code| {
+            let start = *bit;
+            *bit += usize::from(code.size);
+            Some((start, code))
+        });
+        let mut next_code = codes.next();
+        let mut subblock_end = 0;
+        let descriptions: Vec<String> = image_data
+            .data
+            .slices()
+            .map(|subblock| {
+                subblock_end += subblock.len() * 8;
+                let mut subblock_codes = Vec::new();
+                while let Some((start, code)) = next_code {
+                    if start >= subblock_end {
+                        break;
+                    }
+                    subblock_codes.push(code);
+                    next_code = codes.next();
+                }
+                lzw_codes(&subblock_codes, clear_code)
+            })
+            .collect();
+
+        self.subblocks(image_data.data, |index, _| descriptions[index].clone());
+        match decoded {
+            Ok(indices) => self.note(&format!("decoded {} of {} pixels", indices.len(), pixels)),
+            Err(error) => self.note(&format!("decoding error: {}", error)),
+        }
+    }
+
+    fn block(&mut self, block: &Block<'_>) {
+        match block {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } => {
+                if let Some(graphic_control_extension) = graphic_control_extension {
+                    self.graphic_control_extension(graphic_control_extension);
+                }
+                self.image_descriptor(image_descriptor);
+                if let Some(local_color_table) = local_color_table {
+                    self.section("Local Color Table");
+                    self.color_table(local_color_table);
+                }
+                self.section("Table Based Image Data");
+                self.field(
+                    1,
+                    format!(
+                        "LZW minimum code size: {}",
+                        image_data.lzw_minimum_code_size
+                    ),
+                );
+                self.image_data(image_descriptor, image_data);
+            }
+            Block::TextBlock {
+                graphic_control_extension,
+                text: data,
+            } => {
+                if let Some(graphic_control_extension) = graphic_control_extension {
+                    self.graphic_control_extension(graphic_control_extension);
+                }
+                self.section("Plain Text Extension");
+                self.field(1, "extension introducer");
+                self.field(1, "plain text label");
+                self.subblocks(*data, |index, subblock| match (index, subblock.len()) {
+                    (0, 12) => plain_text_header(subblock),
+                    _ => text(subblock),
+                });
+            }
+            Block::ApplicationExtension(data) => {
+                self.section("Application Extension");
+                self.field(1, "extension introducer");
+                self.field(1, "application extension label");
+                self.subblocks(*data, |index, subblock| match (index, subblock.len()) {
+                    (0, 11) => format!(
+                        "application identifier: {}, authentication code: {}",
+                        text(&subblock[..8]),
+                        text(&subblock[8..])
+                    ),
+                    _ => "application data".to_string(),
+                });
+            }
+            Block::CommentExtension(data) => {
+                self.extension("Comment Extension", "comment label", *data)
+            }
+            Block::GraphicControlExtension(graphic_control_extension) => {
+                self.graphic_control_extension(graphic_control_extension)
+            }
+            Block::UnknownExtension { label, data } => self.extension(
+                "Unknown Extension",
+                &format!("extension label: 0x{:02x}", label),
+                *data,
+            ),
+        }
+    }
+}
+
+fn yes_no(flag: bool) -> &'static str {
+    if flag {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Describes the first sub-block of a Plain Text Extension.
+///
+/// See the GIF89a spec §25
+fn plain_text_header(subblock: &[u8]) -> String {
+    let u16_at = |i: usize| u16::from_le_bytes([subblock[i], subblock[i + 1]]);
+    format!(
+        "text grid: {}x{}+{}+{}, character cell: {}x{}, foreground color: {}, background color: {}",
+        u16_at(4),
+        u16_at(6),
+        u16_at(0),
+        u16_at(2),
+        subblock[8],
+        subblock[9],
+        subblock[10],
+        subblock[11]
+    )
+}
+
+/// Quotes printable ASCII, replacing anything else with '.'
+fn text(bytes: &[u8]) -> String {
+    let text: String = bytes
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("\"{}\"", text)
+}
+
+/// Lists the first LZW codes of a sub-block, naming the Clear and End of Information codes.
+fn lzw_codes(codes: &[&LZWCode], clear_code: u16) -> String {
+    let mut shown: Vec<String> = codes
+        .iter()
+        .take(CODES_PER_SUBBLOCK)
+        .map(|code| match code.code {
+            code if code == clear_code => "clear".to_string(),
+            code if code == clear_code + 1 => "end".to_string(),
+            code => code.to_string(),
+        })
+        .collect();
+    if codes.len() > CODES_PER_SUBBLOCK {
+        shown.push("...".to_string());
+    }
+    format!("LZW codes: {} ({} codes)", shown.join(" "), codes.len())
+}
+
+/// Prints every structure of a GIF with its offset, raw bytes and decoded meaning, followed by
+/// why parsing stopped before the Trailer, if it did.
+pub fn print(gif: &GIF<'_>, gif_data: &[u8], error: Option<ParsingError>) {
+    let mut dump = Dump {
+        gif_data,
+        offset: 0,
+    };
+
+    println!(
+        "{:<8}  {:<width$}  meaning",
+        "offset",
+        "bytes",
+        width = BYTES_PER_LINE * 3 - 1
+    );
+
+    dump.section("Header");
+    dump.field(3, "signature");
+    dump.field(3, format!("version: {}", text(&gif_data[3..6])));
+
+    dump.section("Logical Screen Descriptor");
+    dump.field(2, format!("logical screen width: {}", gif.width));
+    dump.field(2, format!("logical screen height: {}", gif.height));
+    dump.field(
+        1,
+        format!(
+            "global color table: {}, color resolution: {} bits, sorted: {}, size: {} colors",
+            yes_no(gif.global_color_table.is_some()),
+            ((gif.packed_field & 0b_0111_0000) >> 4) + 1,
+            yes_no(gif.packed_field & 0b_0000_1000 != 0),
+            2 << (gif.packed_field & 0b_0000_0111)
+        ),
+    );
+    dump.field(
+        1,
+        format!("background color index: {}", gif.background_color_index),
+    );
+    dump.field(1, format!("pixel aspect ratio: {}", gif.pixel_aspect_ratio));
+
+    if let Some(global_color_table) = gif.global_color_table {
+        dump.section("Global Color Table");
+        dump.color_table(global_color_table);
+    }
+
+    for block in &gif.data {
+        dump.block(block);
+    }
+
+    if gif.trailer {
+        dump.section("Trailer");
+        dump.field(1, "trailer");
+    }
+    if let Some(error) = error {
+        dump.section(&format!("Parsing Error: {}", error));
+    }
+    if dump.offset < gif_data.len() {
+        let len = gif_data.len() - dump.offset;
+        dump.section("Unparsed Data");
+        dump.field(len, format!("{} bytes", len));
+    }
+}
@@ -0,0 +1,96 @@
+use gift::{Renderer, RgbaImage, GIF};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Copy, Clone)]
+enum Format {
+    // Portable Arbitrary Map with an alpha channel
+    Pam,
+    // Portable Pixmap, dropping the alpha channel
+    Ppm,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Pam => "pam",
+            Format::Ppm => "ppm",
+        }
+    }
+}
+
+fn write_image(image: &RgbaImage, format: Format, path: &Path) -> Result<(), String> {
+    let write = || -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Pam => {
+                write!(
+                    writer,
+                    "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+                    image.width, image.height
+                )?;
+                writer.write_all(&image.pixels)?;
+            }
+            Format::Ppm => {
+                write!(writer, "P6\n{} {}\n255\n", image.width, image.height)?;
+                for pixel in image.pixels.chunks(4) {
+                    writer.write_all(&pixel[..3])?;
+                }
+            }
+        }
+        writer.flush()
+    };
+    write().map_err(|err| format!("cannot write {}: {}", path.display(), err))
+}
+
+/// Writes every composited frame, and optionally every un-composited image, to `directory` as
+/// `frame-<number>-<delay>ms.<format>` and `raw-<number>-<delay>ms.<format>` respectively.
+pub fn extract(gif: &GIF<'_>, directory: &str, options: &[&str]) -> Result<(), String> {
+    let mut raw = false;
+    let mut format = Format::Pam;
+    for option in options {
+        match *option {
+            "--raw" => raw = true,
+            "--ppm" => format = Format::Ppm,
+            _ => return Err(format!("unknown option {}", option)),
+        }
+    }
+
+    let directory = Path::new(directory);
+    fs::create_dir_all(directory)
+        .map_err(|err| format!("cannot create {}: {}", directory.display(), err))?;
+
+    // The images along with the Graphic Control Extensions that apply to them, stray ones included
+    let frames: Vec<_> = gif.frames().collect();
+    for (number, frame) in Renderer::new(gif).enumerate() {
+        let frame = frame.map_err(|err| format!("cannot decode frame {}: {}", number, err))?;
+        let delay = u32::from(frame.delay_time) * 10;
+
+        let path = directory.join(format!(
+            "frame-{:04}-{}ms.{}",
+            number,
+            delay,
+            format.extension()
+        ));
+        write_image(&frame.image, format, &path)?;
+
+        if let Some(image_frame) = frames
+            .iter()
+            .find(|image_frame| raw && image_frame.block == frame.block)
+        {
+            let image = image_frame
+                .image()
+                .map_err(|err| format!("cannot decode frame {}: {}", number, err))?;
+            let path = directory.join(format!(
+                "raw-{:04}-{}ms.{}",
+                number,
+                delay,
+                format.extension()
+            ));
+            write_image(&image, format, &path)?;
+        }
+    }
+
+    Ok(())
+}
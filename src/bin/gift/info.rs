@@ -0,0 +1,164 @@
+use gift::{Block, DelayPolicy, DisposalMethod, GIFVersion, GraphicControlExtension, GIF};
+
+fn color_table_size(color_table: Option<&[u8]>) -> String {
+    match color_table {
+        Some(color_table) => format!("{} colors", color_table.len() / 3),
+        None => "none".to_string(),
+    }
+}
+
+fn block_type(block: &Block<'_>) -> String {
+    match block {
+        Block::GraphicBlock { .. } => "image".to_string(),
+        Block::TextBlock { .. } => "plain text".to_string(),
+        Block::ApplicationExtension(_) => "application".to_string(),
+        Block::CommentExtension(_) => "comment".to_string(),
+        Block::GraphicControlExtension(_) => "control".to_string(),
+        Block::UnknownExtension { label, .. } => format!("unknown 0x{:02x}", label),
+    }
+}
+
+fn disposal_method(disposal_method: DisposalMethod) -> String {
+    match disposal_method {
+        DisposalMethod::Unspecified => "unspecified".to_string(),
+        DisposalMethod::DoNotDispose => "none".to_string(),
+        DisposalMethod::RestoreToBackgroundColor => "background".to_string(),
+        DisposalMethod::RestoreToPrevious => "previous".to_string(),
+        DisposalMethod::Reserved(value) => format!("reserved {}", value),
+    }
+}
+
+/// The delay, disposal and transparency columns.
+fn graphic_control_columns(graphic_control_extension: &Option<GraphicControlExtension>) -> String {
+    match graphic_control_extension {
+        Some(graphic_control_extension) => format!(
+            "{:>6} {:>12} {:>12}",
+            graphic_control_extension.delay_time,
+            disposal_method(graphic_control_extension.disposal_method()),
+            if graphic_control_extension.transparent_color_flag() {
+                graphic_control_extension
+                    .transparent_color_index
+                    .to_string()
+            } else {
+                "-".to_string()
+            }
+        ),
+        None => format!("{:>6} {:>12} {:>12}", "-", "-", "-"),
+    }
+}
+
+pub fn print(gif: &GIF<'_>) {
+    println!(
+        "version:                {}",
+        match gif.version {
+            GIFVersion::GIF87a => "87a",
+            GIFVersion::GIF89a => "89a",
+        }
+    );
+    println!("logical screen:         {}x{}", gif.width, gif.height);
+    println!(
+        "global color table:     {}",
+        color_table_size(gif.global_color_table)
+    );
+    println!("background color index: {}", gif.background_color_index);
+    println!(
+        "loop count:             {}",
+        match gif.loop_count() {
+            Some(0) => "forever".to_string(),
+            Some(loop_count) => loop_count.to_string(),
+            None => "none".to_string(),
+        }
+    );
+    println!(
+        "duration:               {:.2} s ({:.2} s in browsers)",
+        gif.duration(DelayPolicy::Raw).as_secs_f64(),
+        gif.duration(DelayPolicy::Browser).as_secs_f64()
+    );
+    println!();
+    println!(
+        "{:>5} {:<12} {:>8} {:>8} {:>16} {:>6} {:>12} {:>12} {:>12} {:>10}",
+        "block",
+        "type",
+        "offset",
+        "length",
+        "geometry",
+        "delay",
+        "disposal",
+        "transparent",
+        "local colors",
+        "compressed"
+    );
+
+    let mut offset = gif.header_len();
+    for (index, block) in gif.data.iter().enumerate() {
+        let (geometry, graphic_control, local_colors, compressed) = match block {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } => (
+                format!(
+                    "{}x{}+{}+{}",
+                    image_descriptor.width,
+                    image_descriptor.height,
+                    image_descriptor.left,
+                    image_descriptor.top
+                ),
+                graphic_control_columns(graphic_control_extension),
+                local_color_table.map_or("-".to_string(), |local_color_table| {
+                    (local_color_table.len() / 3).to_string()
+                }),
+                image_data.data.into_iter().count().to_string(),
+            ),
+            Block::TextBlock {
+                graphic_control_extension,
+                ..
+            } => (
+                "-".to_string(),
+                graphic_control_columns(graphic_control_extension),
+                "-".to_string(),
+                "-".to_string(),
+            ),
+            Block::GraphicControlExtension(graphic_control_extension) => (
+                "-".to_string(),
+                graphic_control_columns(&Some(*graphic_control_extension)),
+                "-".to_string(),
+                "-".to_string(),
+            ),
+            _ => (
+                "-".to_string(),
+                graphic_control_columns(&None),
+                "-".to_string(),
+                "-".to_string(),
+            ),
+        };
+
+        println!(
+            "{:>5} {:<12} {:>8} {:>8} {:>16} {} {:>12} {:>10}",
+            index,
+            block_type(block),
+            offset,
+            block.encoded_len(),
+            geometry,
+            graphic_control,
+            local_colors,
+            compressed
+        );
+
+        offset += block.encoded_len();
+    }
+}
+
+/// Prints the whole parsed structure as JSON, with byte data in base64.
+#[cfg(feature = "serde")]
+pub fn print_json(gif: &GIF<'_>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(gif).map_err(|err| err.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn print_json(_: &GIF<'_>) -> Result<(), String> {
+    Err("JSON output requires building gift with the \"serde\" feature".to_string())
+}
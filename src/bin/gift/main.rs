@@ -0,0 +1,120 @@
+extern crate gift;
+
+mod build;
+mod concat;
+mod dump;
+mod extract;
+mod info;
+mod optimize;
+
+use gift::{parse_gif_with_ranges, ParsedGIF, GIF};
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "\
+Usage: gift <command> [<args>]
+
+Commands:
+    info [--json] <file>          Print the structure of a GIF, optionally as JSON
+    dump <file>                   Print every byte of a GIF along with its meaning
+    extract [options] <file> <directory>
+                                  Write every frame of a GIF as an image file
+        --raw                     Also write the un-composited images
+        --ppm                     Write PPM files without transparency instead of PAM
+    build <description> <file>    Encode a GIF described in JSON
+    optimize [options] <file> <output>
+                                  Shrink a GIF without changing how it looks
+        --strip-comments          Drop comment extensions
+        --keep-duplicates         Keep frames identical to the previous one
+    concat <file>... <output>     Join GIFs into one animation, playing them in order
+    help                          Print this message";
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("cannot read {}: {}", path, err))
+}
+
+fn parse_with_ranges<'a>(path: &str, gif_data: &'a [u8]) -> Result<ParsedGIF<'a>, String> {
+    parse_gif_with_ranges(gif_data)
+        .map_err(|err| format!("cannot parse {} as a GIF: {}", path, err))
+}
+
+/// Parses a GIF to be read, warning that only the blocks before an error are kept.
+fn parse<'a>(path: &str, gif_data: &'a [u8]) -> Result<GIF<'a>, String> {
+    let parsed = parse_with_ranges(path, gif_data)?;
+    if let Some(error) = parsed.error {
+        eprintln!(
+            "warning: reading {} only up to where parsing stopped: {}",
+            path, error
+        );
+    }
+    Ok(parsed.gif)
+}
+
+/// Parses a GIF to be written out again, failing unless it is complete since the result would
+/// otherwise pass for a valid GIF.
+fn parse_complete<'a>(path: &str, gif_data: &'a [u8]) -> Result<GIF<'a>, String> {
+    let parsed = parse_with_ranges(path, gif_data)?;
+    match parsed.error {
+        Some(error) => Err(format!("cannot parse {} as a GIF: {}", path, error)),
+        None => Ok(parsed.gif),
+    }
+}
+
+/// Splits arguments into options (starting with "--") and positional arguments.
+fn split_options(args: &[String]) -> (Vec<&str>, Vec<&str>) {
+    args.iter()
+        .map(String::as_str)
+        .partition(|arg| arg.starts_with("--"))
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (options, args) = split_options(args);
+
+    match (args.as_slice(), options.as_slice()) {
+        (["info", path], []) => {
+            let gif_data = read_file(path)?;
+            info::print(&parse(path, &gif_data)?);
+            Ok(())
+        }
+        (["info", path], ["--json"]) => {
+            let gif_data = read_file(path)?;
+            info::print_json(&parse(path, &gif_data)?)
+        }
+        (["dump", path], []) => {
+            let gif_data = read_file(path)?;
+            let parsed = parse_with_ranges(path, &gif_data)?;
+            dump::print(&parsed.gif, &gif_data, parsed.error);
+            Ok(())
+        }
+        (["extract", path, directory], options) => {
+            let gif_data = read_file(path)?;
+            extract::extract(&parse(path, &gif_data)?, directory, options)
+        }
+        (["build", description, output], []) => build::build(description, output),
+        (["optimize", path, output], options) => {
+            let gif_data = read_file(path)?;
+            optimize::optimize(
+                &parse_complete(path, &gif_data)?,
+                gif_data.len(),
+                output,
+                options,
+            )
+        }
+        (["concat", paths @ .., output], []) if !paths.is_empty() => concat::concat(paths, output),
+        (["help"], []) => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(message) = run(&args) {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
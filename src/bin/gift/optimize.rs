@@ -0,0 +1,34 @@
+use gift::{GIFOptimizer, GIF};
+
+/// Writes a smaller GIF that displays exactly like the given one.
+pub fn optimize(
+    gif: &GIF<'_>,
+    size: usize,
+    output_path: &str,
+    options: &[&str],
+) -> Result<(), String> {
+    let mut optimizer = GIFOptimizer::new();
+    for option in options {
+        match *option {
+            "--strip-comments" => optimizer.strip_comments = true,
+            "--keep-duplicates" => optimizer.merge_duplicate_frames = false,
+            _ => return Err(format!("unknown option {}", option)),
+        }
+    }
+
+    let mut gif_data = Vec::new();
+    optimizer
+        .optimize(gif)
+        .write(&mut gif_data)
+        .map_err(|err| format!("cannot encode {}: {}", output_path, err))?;
+    std::fs::write(output_path, &gif_data)
+        .map_err(|err| format!("cannot write {}: {}", output_path, err))?;
+
+    println!(
+        "{} -> {} bytes ({:+.1}%)",
+        size,
+        gif_data.len(),
+        (gif_data.len() as f64 / size.max(1) as f64 - 1.0) * 100.0
+    );
+    Ok(())
+}
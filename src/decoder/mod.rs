@@ -0,0 +1,154 @@
+use super::lzw::DecodingError;
+use super::model::GIF;
+use super::parser::parse_gif_with_ranges;
+use super::render::{Compositor, RenderedFrame};
+use ::image::error::{DecodingError as ImageDecodingError, ImageFormatHint};
+use ::image::metadata::LoopCount;
+use ::image::{
+    AnimationDecoder, ColorType, Delay, Frame, Frames, ImageBuffer, ImageDecoder, ImageError,
+    ImageFormat, ImageResult,
+};
+use std::num::NonZeroU32;
+
+fn decoding_error(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> ImageError {
+    ImageError::Decoding(ImageDecodingError::new(
+        ImageFormatHint::Exact(ImageFormat::Gif),
+        err,
+    ))
+}
+
+fn to_frame(rendered_frame: Result<RenderedFrame, DecodingError>) -> ImageResult<Frame> {
+    let rendered_frame = rendered_frame.map_err(decoding_error)?;
+    let image = rendered_frame.image;
+    let buffer = ImageBuffer::from_raw(
+        u32::from(image.width),
+        u32::from(image.height),
+        image.pixels,
+    )
+    .expect("a rendered image has 4 bytes per pixel");
+    // The delay time is in hundredths of a second
+    let delay = Delay::from_numer_denom_ms(u32::from(rendered_frame.delay_time) * 10, 1);
+    Ok(Frame::from_parts(buffer, 0, 0, delay))
+}
+
+/// A decoder for the `image` crate, built on `parse_gif_with_ranges` and `Renderer`.
+///
+/// As an `ImageDecoder` it yields the first frame. As an `AnimationDecoder` it yields every frame
+/// composited on the Logical Screen, so all frames have the size of the GIF.
+///
+/// Truncated or corrupt data is rejected rather than decoded up to the error, since the `image`
+/// crate has no way to tell that frames are missing.
+pub struct GIFDecoder<'a> {
+    gif: GIF<'a>,
+}
+
+impl<'a> GIFDecoder<'a> {
+    pub fn new(gif_data: &'a [u8]) -> ImageResult<GIFDecoder<'a>> {
+        let parsed = parse_gif_with_ranges(gif_data).map_err(decoding_error)?;
+        match parsed.error {
+            Some(error) => Err(decoding_error(error)),
+            None => Ok(GIFDecoder { gif: parsed.gif }),
+        }
+    }
+}
+
+impl ImageDecoder for GIFDecoder<'_> {
+    fn dimensions(&self) -> (u32, u32) {
+        (u32::from(self.gif.width), u32::from(self.gif.height))
+    }
+
+    fn color_type(&self) -> ColorType {
+        ColorType::Rgba8
+    }
+
+    fn read_image(self, buf: &mut [u8]) -> ImageResult<()> {
+        assert_eq!(buf.len() as u64, self.total_bytes());
+        match Compositor::new(&self.gif).next_frame(&self.gif) {
+            Some(rendered_frame) => {
+                let rendered_frame = rendered_frame.map_err(decoding_error)?;
+                buf.copy_from_slice(&rendered_frame.image.pixels);
+            }
+            // A GIF without images shows a transparent Logical Screen
+            None => buf.iter_mut().for_each(|byte| *byte = 0),
+        }
+        Ok(())
+    }
+
+    fn read_image_boxed(self: Box<Self>, buf: &mut [u8]) -> ImageResult<()> {
+        (*self).read_image(buf)
+    }
+}
+
+impl<'a> AnimationDecoder<'a> for GIFDecoder<'a> {
+    fn into_frames(self) -> Frames<'a> {
+        let mut compositor = Compositor::new(&self.gif);
+        let gif = self.gif;
+        Frames::new(Box::new(std::iter::from_fn(move || {
+            compositor.next_frame(&gif).map(to_frame)
+        })))
+    }
+
+    /// A GIF without a looping application extension plays once, and a loop count of 0 loops
+    /// forever.
+    fn loop_count(&self) -> LoopCount {
+        match self.gif.loop_count() {
+            None => LoopCount::Finite(NonZeroU32::MIN),
+            Some(0) => LoopCount::Infinite,
+            Some(loop_count) => LoopCount::Finite(
+                NonZeroU32::new(u32::from(loop_count) + 1).expect("loop count is at least 2"),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{Encoder, IndexedFrame};
+
+    fn encode(loop_count: Option<u16>) -> Vec<u8> {
+        let mut encoder = Encoder::new(2, 1, Some(&[0, 0, 0, 255, 255, 255])).unwrap();
+        if let Some(loop_count) = loop_count {
+            encoder.set_loop_count(loop_count);
+        }
+        let mut frame = IndexedFrame::new(0, 0, 2, 1, 1);
+        frame.delay_time = 5;
+        encoder.add_frame(&frame).unwrap();
+        let mut gif_data = vec![];
+        encoder.write(&mut gif_data).unwrap();
+        gif_data
+    }
+
+    #[test]
+    fn should_map_loop_count_to_plays() {
+        // `LoopCount` implements neither `PartialEq` nor `Debug`
+        let plays = |gif_data: &[u8]| match GIFDecoder::new(gif_data).unwrap().loop_count() {
+            LoopCount::Infinite => None,
+            LoopCount::Finite(plays) => Some(plays.get()),
+        };
+        assert_eq!(plays(&encode(None)), Some(1));
+        assert_eq!(plays(&encode(Some(0))), None);
+        assert_eq!(plays(&encode(Some(2))), Some(3));
+    }
+
+    #[test]
+    fn should_read_first_frame_as_rgba() {
+        let gif_data = encode(None);
+        let decoder = GIFDecoder::new(&gif_data).unwrap();
+        let mut buf = vec![0; 8];
+        decoder.read_image(&mut buf).unwrap();
+        assert_eq!(buf, [255, 255, 255, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn should_reject_invalid_data() {
+        assert!(GIFDecoder::new(b"PNG").is_err());
+    }
+
+    #[test]
+    fn should_reject_truncated_data() {
+        let gif_data = encode(None);
+        assert!(GIFDecoder::new(&gif_data).is_ok());
+        assert!(GIFDecoder::new(&gif_data[..gif_data.len() - 4]).is_err());
+    }
+}
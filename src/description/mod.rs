@@ -0,0 +1,225 @@
+use super::encoder::{Encoder, EncodingError, IndexedFrame};
+use super::model::DisposalMethod;
+use super::quantize::{QuantizationMethod, Quantizer};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A readable description of a GIF, which can be authored by hand (e.g. as JSON with the `serde`
+/// feature) and encoded into an actual GIF.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GIFDescription {
+    pub width: u16,
+    pub height: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub global_color_table: Option<Vec<[u8; 3]>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub background_color_index: u8,
+    // Number of repetitions, where 0 means forever
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub loop_count: Option<u16>,
+    pub frames: Vec<FrameDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameDescription {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub left: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub top: u16,
+    // Defaults to the width of the Logical Screen
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub width: Option<u16>,
+    // Defaults to the height of the Logical Screen
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub height: Option<u16>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub local_color_table: Option<Vec<[u8; 3]>>,
+    // In hundredths of a second
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub delay_time: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub disposal_method: Option<DisposalMethod>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub transparent_color_index: Option<u8>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interlaced: bool,
+    pub pixels: Pixels,
+}
+
+/// The pixels of a frame in row order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Pixels {
+    // Indices into the frame's color table
+    Indices(Vec<u8>),
+    // Colors that must appear in the frame's color table. Fully transparent pixels are mapped to
+    // the transparent color index. Without any color table, the pixels are quantized into a Local
+    // Color Table instead.
+    Rgba(Vec<[u8; 4]>),
+}
+
+fn flatten(color_table: &[[u8; 3]]) -> Vec<u8> {
+    color_table
+        .iter()
+        .flat_map(|color| color.iter().cloned())
+        .collect()
+}
+
+fn rgba_to_indices(
+    pixels: &[[u8; 4]],
+    color_table: &[[u8; 3]],
+    transparent_color_index: Option<u8>,
+) -> Result<Vec<u8>, EncodingError> {
+    pixels
+        .iter()
+        .map(|pixel| match (pixel[3], transparent_color_index) {
+            (0, Some(transparent_color_index)) => Ok(transparent_color_index),
+            _ => color_table
+                .iter()
+                .position(|color| color[..] == pixel[..3])
+                .map(|index| index as u8)
+                .ok_or(EncodingError::ColorNotInTable(*pixel)),
+        })
+        .collect()
+}
+
+impl GIFDescription {
+    /// Encodes all the frames.
+    pub fn encode(&self) -> Result<Encoder, EncodingError> {
+        let global_color_table = self.global_color_table.as_ref().map(|table| flatten(table));
+        let mut encoder = Encoder::new(self.width, self.height, global_color_table.as_deref())?;
+        encoder.set_background_color_index(self.background_color_index);
+        if let Some(loop_count) = self.loop_count {
+            encoder.set_loop_count(loop_count);
+        }
+
+        for frame in &self.frames {
+            let width = frame.width.unwrap_or(self.width);
+            let height = frame.height.unwrap_or(self.height);
+            let color_table = frame
+                .local_color_table
+                .as_ref()
+                .or(self.global_color_table.as_ref());
+            let mut indexed_frame = match (&frame.pixels, color_table) {
+                (Pixels::Rgba(pixels), None) => {
+                    let pixels: Vec<_> = pixels
+                        .iter()
+                        .flat_map(|pixel| pixel.iter().cloned())
+                        .collect();
+                    let quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+                    IndexedFrame::from_rgba(
+                        frame.left, frame.top, width, height, &pixels, &quantizer,
+                    )?
+                }
+                (pixels, color_table) => IndexedFrame {
+                    indices: match pixels {
+                        Pixels::Indices(indices) => indices.clone(),
+                        Pixels::Rgba(pixels) => rgba_to_indices(
+                            pixels,
+                            color_table.ok_or(EncodingError::MissingColorTable)?,
+                            frame.transparent_color_index,
+                        )?,
+                    },
+                    local_color_table: frame.local_color_table.as_ref().map(|table| flatten(table)),
+                    transparent_color_index: frame.transparent_color_index,
+                    ..IndexedFrame::new(frame.left, frame.top, width, height, 0)
+                },
+            };
+            indexed_frame.delay_time = frame.delay_time;
+            indexed_frame.disposal_method =
+                frame.disposal_method.unwrap_or(DisposalMethod::Unspecified);
+            indexed_frame.interlaced = frame.interlaced;
+
+            encoder.add_frame(&indexed_frame)?;
+        }
+
+        Ok(encoder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render::Renderer;
+    use super::*;
+
+    fn frame(pixels: Pixels) -> FrameDescription {
+        FrameDescription {
+            left: 0,
+            top: 0,
+            width: None,
+            height: None,
+            local_color_table: None,
+            delay_time: 10,
+            disposal_method: None,
+            transparent_color_index: Some(2),
+            interlaced: false,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn should_encode_indices_and_rgba_pixels() {
+        let description = GIFDescription {
+            width: 2,
+            height: 1,
+            global_color_table: Some(vec![[255, 0, 0], [0, 0, 255], [0, 0, 0]]),
+            background_color_index: 0,
+            loop_count: Some(0),
+            frames: vec![
+                frame(Pixels::Indices(vec![0, 1])),
+                frame(Pixels::Rgba(vec![[0, 0, 255, 255], [0, 0, 0, 0]])),
+            ],
+        };
+
+        let encoder = description.encode().unwrap();
+        let gif = encoder.gif();
+        assert_eq!(gif.loop_count(), Some(0));
+
+        let frames: Vec<_> = Renderer::new(&gif).map(Result::unwrap).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].delay_time, 10);
+        assert_eq!(frames[1].image.pixel(0, 0), [0, 0, 255, 255]);
+        // the transparent pixel leaves the first frame visible
+        assert_eq!(frames[1].image.pixel(1, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn should_quantize_rgba_pixels_without_color_table() {
+        let mut rgba_frame = frame(Pixels::Rgba(vec![[0, 255, 0, 255], [0, 0, 0, 0]]));
+        rgba_frame.transparent_color_index = None;
+        let description = GIFDescription {
+            width: 2,
+            height: 1,
+            global_color_table: None,
+            background_color_index: 0,
+            loop_count: None,
+            frames: vec![rgba_frame],
+        };
+
+        let encoder = description.encode().unwrap();
+        let gif = encoder.gif();
+        let frames: Vec<_> = Renderer::new(&gif).map(Result::unwrap).collect();
+        assert_eq!(frames[0].image.pixel(0, 0), [0, 255, 0, 255]);
+        assert_eq!(frames[0].image.pixel(1, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn should_reject_colors_missing_from_color_table() {
+        let description = GIFDescription {
+            width: 1,
+            height: 1,
+            global_color_table: Some(vec![[255, 0, 0]]),
+            background_color_index: 0,
+            loop_count: None,
+            frames: vec![frame(Pixels::Rgba(vec![[0, 255, 0, 255]]))],
+        };
+        assert_eq!(
+            description.encode().err(),
+            Some(EncodingError::ColorNotInTable([0, 255, 0, 255]))
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use super::super::encoder::{Encoder, IndexedFrame, OwnedBlock};
+use super::super::model::{Block, DisposalMethod, GIFVersion, GIF};
+use super::super::quantize::{QuantizationMethod, Quantizer};
+use super::super::render::RgbaImage;
+use super::{header, is_image, render, EditError, EditedGIF};
+
+/// A GIF to concatenate, once placed on the combined Logical Screen.
+struct Part<'a, 'b> {
+    gif: &'b GIF<'a>,
+    left: u16,
+    top: u16,
+    // The frames of the GIF rendered on its own, drawn on the combined Logical Screen
+    frames: Vec<(RgbaImage, u16)>,
+    // The position of each of its images in the combined blocks
+    images: Vec<usize>,
+}
+
+/// Moves an image of a GIF onto the combined Logical Screen, turning the Global Color Table of
+/// the GIF into a Local Color Table when it differs from the combined one.
+///
+/// An image without any color table cannot be kept from drawing with the combined Global Color
+/// Table, so it is rejected unless there is no such table either.
+fn place_image<'a>(
+    block: Block<'a>,
+    part: &Part<'a, '_>,
+    global_color_table: Option<&'a [u8]>,
+) -> Result<Block<'a>, EditError> {
+    match block {
+        Block::GraphicBlock {
+            graphic_control_extension,
+            mut image_descriptor,
+            mut local_color_table,
+            image_data,
+        } => {
+            image_descriptor.left = image_descriptor.left.saturating_add(part.left);
+            image_descriptor.top = image_descriptor.top.saturating_add(part.top);
+            if local_color_table.is_none() && part.gif.global_color_table != global_color_table {
+                match part.gif.global_color_table {
+                    Some(color_table) => {
+                        local_color_table = Some(color_table);
+                        image_descriptor.packed_field = image_descriptor.packed_field
+                            & 0b_0111_1000
+                            | 0b_1000_0000
+                            | part.gif.packed_field & 0b_0000_0111;
+                    }
+                    None => return Err(EditError::MissingColorTable),
+                }
+            }
+            Ok(Block::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            })
+        }
+        block => Ok(block),
+    }
+}
+
+/// Replaces the last frame of a part with its composited canvas, which is cleared afterwards so
+/// that the next part starts out on a transparent canvas.
+fn clearing_frame(
+    part: &Part<'_, '_>,
+    width: u16,
+    height: u16,
+) -> Result<OwnedBlock<'static>, EditError> {
+    let (canvas, delay_time) = &part.frames[part.frames.len() - 1];
+    let image = canvas.crop(part.left, part.top, part.gif.width, part.gif.height);
+    let quantized =
+        Quantizer::new(QuantizationMethod::MedianCut).quantize(image.width, &image.pixels);
+
+    let frame = IndexedFrame {
+        indices: quantized.indices,
+        local_color_table: Some(quantized.color_table),
+        delay_time: *delay_time,
+        disposal_method: DisposalMethod::RestoreToBackgroundColor,
+        transparent_color_index: quantized.transparent_color_index,
+        ..IndexedFrame::new(part.left, part.top, image.width, image.height, 0)
+    };
+    let encoder = Encoder::new(width, height, None)?;
+    Ok(OwnedBlock::Encoded(encoder.encode_frame(&frame)?))
+}
+
+/// Joins GIFs into a single animation, one after the other.
+///
+/// The Logical Screen is large enough for every GIF, the smaller ones being centered on it. The
+/// first GIF provides the Global Color Table and the loop count, the images of the other GIFs
+/// getting their own Global Color Table as a Local Color Table when it differs, which fails for
+/// images without any color table. Delays are kept, and when a GIF would show through the next
+/// one, its last frame is composited again and cleared.
+pub fn concat_gifs<'a>(gifs: &[GIF<'a>]) -> Result<EditedGIF<'a>, EditError> {
+    let first = gifs.first().ok_or(EditError::NoInput)?;
+    let width = gifs.iter().map(|gif| gif.width).max().unwrap_or(0);
+    let height = gifs.iter().map(|gif| gif.height).max().unwrap_or(0);
+    if width == 0 || height == 0 {
+        return Err(EditError::InvalidSize);
+    }
+
+    let mut parts = Vec::with_capacity(gifs.len());
+    let mut blocks = Vec::new();
+    for (index, gif) in gifs.iter().enumerate() {
+        let mut part = Part {
+            gif,
+            left: (width - gif.width) / 2,
+            top: (height - gif.height) / 2,
+            frames: Vec::new(),
+            images: Vec::new(),
+        };
+        for frame in render(gif)? {
+            let mut canvas = RgbaImage::new(width, height);
+            canvas.draw(&frame.image, part.left, part.top);
+            part.frames.push((canvas, frame.delay_time));
+        }
+        for block in &gif.data {
+            // only the loop count of the first GIF applies
+            if index > 0 && block.loop_count().is_some() {
+                continue;
+            }
+            if is_image(block) {
+                part.images.push(blocks.len());
+            }
+            blocks.push(OwnedBlock::Borrowed(place_image(
+                *block,
+                &part,
+                first.global_color_table,
+            )?));
+        }
+        parts.push(part);
+    }
+
+    let mut concatenated = EditedGIF {
+        header: GIF {
+            width,
+            height,
+            ..header(first)
+        },
+        global_color_table: None,
+        blocks,
+    };
+    if gifs.iter().any(|gif| gif.version == GIFVersion::GIF89a) {
+        concatenated.header.version = GIFVersion::GIF89a;
+    }
+
+    // clear the canvas between two GIFs wherever the first one shows through the second one
+    let mut cleared = vec![false; parts.len()];
+    loop {
+        let rendered = render(&concatenated.gif())?;
+        let mut frames = rendered.iter();
+        let mismatch = parts.iter().position(|part| {
+            part.frames
+                .iter()
+                .any(|(expected, _)| frames.next().map(|frame| &frame.image) != Some(expected))
+        });
+        let previous = match mismatch {
+            Some(mismatch) => parts[..mismatch]
+                .iter()
+                .rposition(|part| !part.images.is_empty()),
+            None => None,
+        };
+        match previous {
+            Some(previous) if !cleared[previous] => {
+                cleared[previous] = true;
+                let part = &parts[previous];
+                concatenated.blocks[part.images[part.images.len() - 1]] =
+                    clearing_frame(part, width, height)?;
+                concatenated.header.version = GIFVersion::GIF89a;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(concatenated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{animation, images};
+    use super::*;
+
+    #[test]
+    fn should_concatenate_gifs() {
+        let first = animation();
+        let mut second = Encoder::new(2, 2, Some(&[0, 255, 0, 255, 255, 255])).unwrap();
+        second.set_loop_count(3);
+        let mut frame = IndexedFrame::new(0, 0, 2, 1, 0);
+        frame.delay_time = 50;
+        second.add_frame(&frame).unwrap();
+        second.add_frame(&IndexedFrame::new(1, 1, 1, 1, 1)).unwrap();
+        let (first, second) = (first.gif(), second.gif());
+
+        let concatenated = concat_gifs(&[first.clone(), second.clone(), first.clone()]).unwrap();
+        let concatenated_gif = concatenated.gif();
+        assert_eq!((concatenated_gif.width, concatenated_gif.height), (4, 4));
+        assert_eq!(concatenated_gif.loop_count(), Some(0));
+        assert_eq!(
+            concatenated_gif.global_color_table,
+            first.global_color_table
+        );
+
+        // the second GIF is centered, without the animation of the first one showing through
+        let mut expected = images(&first);
+        for (delay_time, image) in images(&second) {
+            let mut canvas = RgbaImage::new(4, 4);
+            canvas.draw(&image, 1, 1);
+            expected.push((delay_time, canvas));
+        }
+        expected.extend(images(&first));
+        assert_eq!(images(&concatenated_gif), expected);
+    }
+
+    #[test]
+    fn should_reject_no_gifs() {
+        assert_eq!(concat_gifs(&[]).map(|_| ()), Err(EditError::NoInput));
+    }
+
+    #[test]
+    fn should_reject_images_without_color_table() {
+        let first = animation();
+        let first = first.gif();
+        let mut second = first.clone();
+        second.global_color_table = None;
+        second.packed_field &= 0b_0111_0000;
+
+        assert_eq!(
+            concat_gifs(&[first.clone(), second.clone()]).map(|_| ()),
+            Err(EditError::MissingColorTable)
+        );
+        // without any Global Color Table, all images draw the same way as on their own
+        assert!(concat_gifs(&[second.clone(), second]).is_ok());
+    }
+}
@@ -0,0 +1,198 @@
+use super::super::encoder::{Encoder, IndexedFrame, OwnedBlock};
+use super::super::frame::Frame;
+use super::super::model::{Block, GIF};
+use super::super::quantize::{QuantizationMethod, Quantizer};
+use super::super::render::RgbaImage;
+use super::{header, render, EditError, EditedGIF};
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Explosion {
+    // Every image on its own, as it is stored
+    Raw,
+    // Every frame as displayed, once the previous frames are composited and disposed of
+    Composited,
+}
+
+/// Looks up the pixels of an image in a color table, keeping the transparent color index for
+/// transparent pixels. `None` if a color is missing, or if no index is left for transparency.
+pub(super) fn index_pixels(
+    image: &RgbaImage,
+    color_table: &[u8],
+    transparent_color_index: Option<u8>,
+) -> Option<(Vec<u8>, Option<u8>)> {
+    let mut indices_by_color = HashMap::new();
+    for (index, color) in color_table.chunks_exact(3).enumerate().rev() {
+        if Some(index as u8) != transparent_color_index {
+            indices_by_color.insert(color, index as u8);
+        }
+    }
+
+    let mut indices = Vec::with_capacity(image.pixels.len() / 4);
+    let mut has_transparency = false;
+    for pixel in image.pixels.chunks_exact(4) {
+        if pixel[3] == 0 {
+            has_transparency = true;
+            indices.push(0);
+        } else {
+            indices.push(*indices_by_color.get(&pixel[..3])?);
+        }
+    }
+    if !has_transparency {
+        return Some((indices, None));
+    }
+
+    // any index that is not used by an opaque pixel
+    let transparent_color_index = transparent_color_index.or_else(|| {
+        let mut used = [false; 256];
+        for (&index, pixel) in indices.iter().zip(image.pixels.chunks_exact(4)) {
+            used[index as usize] |= pixel[3] != 0;
+        }
+        (0..color_table.len() / 3)
+            .find(|&index| !used[index])
+            .map(|index| index as u8)
+    })?;
+    for (index, pixel) in indices.iter_mut().zip(image.pixels.chunks_exact(4)) {
+        if pixel[3] == 0 {
+            *index = transparent_color_index;
+        }
+    }
+    Some((indices, Some(transparent_color_index)))
+}
+
+/// Encodes a composited frame as a GIF of its own, with the color table of its image when all of
+/// its colors are found there, and quantizing it otherwise.
+fn composited_gif<'a>(
+    gif: &GIF<'a>,
+    frame: &Frame<'a>,
+    image: &RgbaImage,
+) -> Result<EditedGIF<'a>, EditError> {
+    let color_table = frame.color_table;
+    let transparent_color_index = frame.transparent_color_index();
+
+    let exact = color_table.and_then(|color_table| {
+        let (indices, transparent_color_index) =
+            index_pixels(image, color_table, transparent_color_index)?;
+        Some((color_table.to_vec(), indices, transparent_color_index))
+    });
+    let (color_table, indices, transparent_color_index) = exact.unwrap_or_else(|| {
+        let quantized =
+            Quantizer::new(QuantizationMethod::MedianCut).quantize(image.width, &image.pixels);
+        (
+            quantized.color_table,
+            quantized.indices,
+            quantized.transparent_color_index,
+        )
+    });
+    let colors = color_table.len() / 3;
+
+    let encoder = Encoder::new(gif.width, gif.height, Some(&color_table))?;
+    let image = encoder.encode_frame(&IndexedFrame {
+        indices,
+        transparent_color_index,
+        ..IndexedFrame::new(0, 0, gif.width, gif.height, 0)
+    })?;
+    let encoded = encoder.gif();
+    let mut exploded = EditedGIF {
+        header: GIF {
+            packed_field: encoded.packed_field,
+            background_color_index: if (gif.background_color_index as usize) < colors {
+                gif.background_color_index
+            } else {
+                0
+            },
+            global_color_table: None,
+            ..header(gif)
+        },
+        global_color_table: encoded.global_color_table.map(<[u8]>::to_vec),
+        blocks: vec![OwnedBlock::Encoded(image)],
+    };
+    exploded.header.version = exploded.gif().required_version();
+    Ok(exploded)
+}
+
+/// Splits an animation into one GIF per frame, each with the Logical Screen and the color table
+/// of the original.
+///
+/// Raw GIFs keep the image and its extensions as they are, so that the frames which depend on
+/// the previous ones may be partly transparent, while composited GIFs show each frame as it is
+/// displayed.
+pub fn explode_gif<'a>(
+    gif: &GIF<'a>,
+    explosion: Explosion,
+) -> Result<Vec<EditedGIF<'a>>, EditError> {
+    match explosion {
+        Explosion::Raw => Ok(gif
+            .frames()
+            .map(|frame| {
+                let mut block = gif.data[frame.block];
+                // a stray Graphic Control Extension is attached to the image it applies to
+                if let Block::GraphicBlock {
+                    graphic_control_extension,
+                    ..
+                } = &mut block
+                {
+                    *graphic_control_extension = frame.graphic_control_extension;
+                }
+                EditedGIF {
+                    header: header(gif),
+                    global_color_table: None,
+                    blocks: vec![OwnedBlock::Borrowed(block)],
+                }
+            })
+            .collect()),
+        Explosion::Composited => render(gif)?
+            .iter()
+            .zip(gif.frames())
+            .map(|(rendered, frame)| composited_gif(gif, &frame, &rendered.image))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{animation, images};
+    use super::*;
+
+    #[test]
+    fn should_explode_raw_images() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let exploded = explode_gif(&gif, Explosion::Raw).unwrap();
+        assert_eq!(exploded.len(), 4);
+        for (exploded, block) in exploded.iter().zip(&gif.data[1..]) {
+            let exploded_gif = exploded.gif();
+            assert_eq!(exploded_gif.data, vec![*block]);
+            assert_eq!(exploded_gif.global_color_table, gif.global_color_table);
+            assert_eq!((exploded_gif.width, exploded_gif.height), (4, 4));
+        }
+    }
+
+    #[test]
+    fn should_explode_composited_frames() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let exploded = explode_gif(&gif, Explosion::Composited).unwrap();
+        let original = images(&gif);
+        assert_eq!(exploded.len(), original.len());
+        for (exploded, (_, image)) in exploded.iter().zip(&original) {
+            let exploded_gif = exploded.gif();
+            // the colors are all found in the original Global Color Table
+            assert_eq!(exploded_gif.global_color_table, gif.global_color_table);
+            assert_eq!(images(&exploded_gif), vec![(0, image.clone())]);
+        }
+
+        // colors from several tables are quantized again
+        let mut encoder = Encoder::new(2, 1, Some(&[255, 0, 0])).unwrap();
+        encoder
+            .add_frame(&IndexedFrame::new(0, 0, 1, 1, 0))
+            .unwrap();
+        let mut frame = IndexedFrame::new(1, 0, 1, 1, 0);
+        frame.local_color_table = Some(vec![0, 0, 255]);
+        encoder.add_frame(&frame).unwrap();
+        let gif = encoder.gif();
+        let exploded = explode_gif(&gif, Explosion::Composited).unwrap();
+        let (_, last) = &images(&exploded[1].gif())[0];
+        assert_eq!(last.pixels, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,449 @@
+use super::super::encoder::{EncodedImage, OwnedBlock};
+use super::super::lzw::{encode, minimum_code_size};
+use super::super::model::{Block, DisposalMethod, GraphicControlExtension, ImageDescriptor, GIF};
+use super::super::render::{decode_indices, interlaced_rows, RgbaImage};
+use super::{encode_composited, header, render, split_blocks, EditError, EditedGIF};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resampling {
+    // Every pixel takes the color of the closest source pixel, so the color tables are kept
+    Nearest,
+    // Every pixel blends the 4 closest source pixels of the composited frames, which are then
+    // quantized again
+    Bilinear,
+    // Every pixel averages the source pixels it covers, which avoids aliasing when shrinking, and
+    // is otherwise bilinear
+    Area,
+}
+
+/// Compresses indices in row order for an image, interlacing them if it is interlaced.
+fn encode_image(
+    graphic_control_extension: Option<GraphicControlExtension>,
+    image_descriptor: ImageDescriptor,
+    local_color_table: Option<&[u8]>,
+    indices: &[u8],
+) -> EncodedImage {
+    let width = image_descriptor.width as usize;
+    let interlaced;
+    let indices = if image_descriptor.interlace_flag() {
+        interlaced = interlaced_rows(image_descriptor.height as usize)
+            .flat_map(|y| &indices[y * width..(y + 1) * width])
+            .cloned()
+            .collect::<Vec<_>>();
+        &interlaced
+    } else {
+        indices
+    };
+    let colors = indices.iter().max().map_or(0, |&index| index as usize + 1);
+    let lzw_minimum_code_size = minimum_code_size(colors);
+    EncodedImage {
+        graphic_control_extension,
+        image_descriptor,
+        local_color_table: local_color_table.map(<[u8]>::to_vec),
+        lzw_minimum_code_size,
+        data: encode(lzw_minimum_code_size, indices),
+    }
+}
+
+/// A single transparent pixel standing in for an image that no longer shows, keeping its delay.
+fn transparent_image(
+    graphic_control_extension: Option<GraphicControlExtension>,
+    local_color_table: Option<&[u8]>,
+    packed_field: u8,
+) -> EncodedImage {
+    let mut graphic_control_extension = graphic_control_extension
+        .unwrap_or_else(|| GraphicControlExtension::new(DisposalMethod::Unspecified, 0, None));
+    let transparent_color_index = if graphic_control_extension.transparent_color_flag() {
+        graphic_control_extension.transparent_color_index
+    } else {
+        0
+    };
+    // nothing to dispose of, and transparency
+    graphic_control_extension.packed_field = graphic_control_extension.packed_field & 0b_1110_0010
+        | DisposalMethod::DoNotDispose.value() << 2
+        | 0b_0000_0001;
+    graphic_control_extension.transparent_color_index = transparent_color_index;
+
+    encode_image(
+        Some(graphic_control_extension),
+        ImageDescriptor {
+            left: 0,
+            top: 0,
+            width: 1,
+            height: 1,
+            packed_field: packed_field & 0b_1011_1111,
+        },
+        local_color_table,
+        &[transparent_color_index],
+    )
+}
+
+/// Transforms every image of a GIF, given the positions of its columns and rows in the new Logical
+/// Screen, in the order of the new pixels. Images whose pixels all disappear are replaced by a
+/// transparent pixel.
+fn map_images<'a>(
+    gif: &GIF<'a>,
+    width: u16,
+    height: u16,
+    columns: impl Fn(u16, u16) -> (u16, Vec<usize>),
+    rows: impl Fn(u16, u16) -> (u16, Vec<usize>),
+) -> Result<EditedGIF<'a>, EditError> {
+    let mut blocks = Vec::with_capacity(gif.data.len());
+    for block in gif.attached_blocks().iter().flatten() {
+        let (graphic_control_extension, image_descriptor, local_color_table, image_data) =
+            match block {
+                Block::GraphicBlock {
+                    graphic_control_extension,
+                    image_descriptor,
+                    local_color_table,
+                    image_data,
+                } => (
+                    graphic_control_extension,
+                    image_descriptor,
+                    local_color_table,
+                    image_data,
+                ),
+                _ => {
+                    blocks.push(OwnedBlock::Borrowed(*block));
+                    continue;
+                }
+            };
+
+        let (left, columns) = columns(image_descriptor.left, image_descriptor.width);
+        let (top, rows) = rows(image_descriptor.top, image_descriptor.height);
+        if columns.is_empty() || rows.is_empty() {
+            blocks.push(OwnedBlock::Encoded(transparent_image(
+                *graphic_control_extension,
+                *local_color_table,
+                image_descriptor.packed_field,
+            )));
+            continue;
+        }
+
+        let indices = decode_indices(image_descriptor, image_data)?;
+        let source_width = image_descriptor.width as usize;
+        let mut mapped = Vec::with_capacity(columns.len() * rows.len());
+        for y in &rows {
+            for x in &columns {
+                mapped.push(indices[y * source_width + x]);
+            }
+        }
+        blocks.push(OwnedBlock::Encoded(encode_image(
+            *graphic_control_extension,
+            ImageDescriptor {
+                left,
+                top,
+                width: columns.len() as u16,
+                height: rows.len() as u16,
+                ..*image_descriptor
+            },
+            *local_color_table,
+            &mapped,
+        )));
+    }
+
+    Ok(EditedGIF {
+        header: GIF {
+            width,
+            height,
+            ..header(gif)
+        },
+        global_color_table: None,
+        blocks,
+    })
+}
+
+/// Crops a GIF to a rectangle of its Logical Screen, cropping every image and moving it relative
+/// to the rectangle. The color tables, delays and disposal methods are kept, while Plain Text
+/// Extensions are left as is.
+pub fn crop_gif<'a>(
+    gif: &GIF<'a>,
+    left: u16,
+    top: u16,
+    width: u16,
+    height: u16,
+) -> Result<EditedGIF<'a>, EditError> {
+    if width == 0
+        || height == 0
+        || u32::from(left) + u32::from(width) > u32::from(gif.width)
+        || u32::from(top) + u32::from(height) > u32::from(gif.height)
+    {
+        return Err(EditError::InvalidRectangle);
+    }
+
+    // the part of an image's span within the span of the rectangle
+    let crop = |start: u16, size: u16, image_start: u16, image_size: u16| {
+        let first = start.max(image_start);
+        let end = (u32::from(start) + u32::from(size))
+            .min(u32::from(image_start) + u32::from(image_size)) as u16;
+        let positions = (first..end.max(first))
+            .map(|position| (position - image_start) as usize)
+            .collect();
+        (first - start, positions)
+    };
+    map_images(
+        gif,
+        width,
+        height,
+        |image_left, image_width| crop(left, width, image_left, image_width),
+        |image_top, image_height| crop(top, height, image_top, image_height),
+    )
+}
+
+/// The position in a span of `size` pixels closest to the center of a position in the span of
+/// `new_size` pixels it is scaled to.
+fn source_position(position: u16, size: u16, new_size: u16) -> f32 {
+    (f32::from(position) + 0.5) * f32::from(size) / f32::from(new_size) - 0.5
+}
+
+/// The source pixels making up each pixel of a span of `size` pixels scaled to `new_size` pixels,
+/// along with their weights.
+fn taps(size: u16, new_size: u16, resampling: Resampling) -> Vec<Vec<(u16, f32)>> {
+    let clamp = |position: f32| position.max(0.0).min(f32::from(size - 1));
+    (0..new_size)
+        .map(|position| match resampling {
+            Resampling::Nearest => {
+                vec![(
+                    clamp(source_position(position, size, new_size).round()) as u16,
+                    1.0,
+                )]
+            }
+            Resampling::Area if new_size < size => {
+                let scale = f32::from(size) / f32::from(new_size);
+                let (start, end) = (f32::from(position) * scale, f32::from(position + 1) * scale);
+                (start.floor() as u16..(end.ceil() as u16).min(size))
+                    .map(|source| {
+                        let covered =
+                            end.min(f32::from(source) + 1.0) - start.max(f32::from(source));
+                        (source, covered / scale)
+                    })
+                    .collect()
+            }
+            _ => {
+                let source = clamp(source_position(position, size, new_size));
+                let (first, weight) = (source.floor() as u16, source.fract());
+                vec![(first, 1.0 - weight), ((first + 1).min(size - 1), weight)]
+            }
+        })
+        .collect()
+}
+
+/// Scales a composited frame, weighting colors by their opacity.
+pub(super) fn scale_image(
+    image: &RgbaImage,
+    width: u16,
+    height: u16,
+    resampling: Resampling,
+) -> RgbaImage {
+    let mut scaled = RgbaImage::new(width, height);
+    let columns = taps(image.width, width, resampling);
+    let rows = taps(image.height, height, resampling);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, column) in columns.iter().enumerate() {
+            let mut sums = [0.0f32; 4];
+            for &(source_y, y_weight) in row {
+                for &(source_x, x_weight) in column {
+                    let pixel = image.pixel(source_x, source_y);
+                    let alpha = f32::from(pixel[3]) * x_weight * y_weight;
+                    for channel in 0..3 {
+                        sums[channel] += f32::from(pixel[channel]) * alpha;
+                    }
+                    sums[3] += alpha;
+                }
+            }
+
+            let offset = 4 * (y * width as usize + x);
+            if sums[3] > 0.0 {
+                for channel in 0..3 {
+                    scaled.pixels[offset + channel] = (sums[channel] / sums[3]).round() as u8;
+                }
+                scaled.pixels[offset + 3] = sums[3].round().min(255.0) as u8;
+            }
+        }
+    }
+    scaled
+}
+
+/// Scales a GIF to a new Logical Screen size.
+///
+/// Nearest neighbour resampling scales every image on its own, keeping the color tables and
+/// extensions, while the other methods composite and quantize the frames again.
+pub fn resize_gif<'a>(
+    gif: &GIF<'a>,
+    width: u16,
+    height: u16,
+    resampling: Resampling,
+) -> Result<EditedGIF<'a>, EditError> {
+    if width == 0 || height == 0 || gif.width == 0 || gif.height == 0 {
+        return Err(EditError::InvalidSize);
+    }
+
+    match resampling {
+        Resampling::Nearest => {
+            // the new positions whose nearest source position falls within an image's span
+            let scale = |size: u16, new_size: u16, image_start: u16, image_size: u16| {
+                let image_end = u32::from(image_start) + u32::from(image_size);
+                let mut first = None;
+                let mut positions = Vec::new();
+                for position in 0..new_size {
+                    let source = source_position(position, size, new_size).round().max(0.0) as u32;
+                    if source >= u32::from(image_start) && source < image_end {
+                        first.get_or_insert(position);
+                        positions.push((source - u32::from(image_start)) as usize);
+                    }
+                }
+                (first.unwrap_or(0), positions)
+            };
+            map_images(
+                gif,
+                width,
+                height,
+                |left, image_width| scale(gif.width, width, left, image_width),
+                |top, image_height| scale(gif.height, height, top, image_height),
+            )
+        }
+        Resampling::Bilinear | Resampling::Area => {
+            let frames: Vec<_> = render(gif)?
+                .into_iter()
+                .map(|frame| {
+                    (
+                        scale_image(&frame.image, width, height, resampling),
+                        frame.delay_time,
+                    )
+                })
+                .collect();
+            let (blocks_before, _, blocks_after) = split_blocks(gif);
+            encode_composited(
+                GIF {
+                    width,
+                    height,
+                    ..header(gif)
+                },
+                &frames,
+                &blocks_before,
+                &blocks_after,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::encoder::{Encoder, IndexedFrame};
+    use super::super::super::frame::detach_graphic_control_extensions;
+    use super::super::tests::{animation, images};
+    use super::*;
+
+    #[test]
+    fn should_crop_images() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let original = images(&gif);
+
+        let cropped = crop_gif(&gif, 0, 0, 2, 3).unwrap();
+        let cropped_gif = cropped.gif();
+        assert_eq!((cropped_gif.width, cropped_gif.height), (2, 3));
+        let expected: Vec<_> = original
+            .iter()
+            .map(|(delay_time, image)| (*delay_time, image.crop(0, 0, 2, 3)))
+            .collect();
+        assert_eq!(images(&cropped_gif), expected);
+
+        assert_eq!(
+            crop_gif(&gif, 3, 0, 2, 2).map(|_| ()),
+            Err(EditError::InvalidRectangle)
+        );
+    }
+
+    #[test]
+    fn should_resize_with_nearest_neighbour() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let original = images(&gif);
+
+        for &(width, height) in &[(8, 8), (6, 3), (2, 2)] {
+            let resized = resize_gif(&gif, width, height, Resampling::Nearest).unwrap();
+            let resized_gif = resized.gif();
+            assert_eq!(resized_gif.global_color_table, gif.global_color_table);
+            let resized_images = images(&resized_gif);
+            assert_eq!(resized_images.len(), original.len());
+            for ((delay_time, resized), (original_delay_time, original)) in
+                resized_images.iter().zip(&original)
+            {
+                assert_eq!(delay_time, original_delay_time);
+                for y in 0..height {
+                    for x in 0..width {
+                        let source = |position, size, new_size| {
+                            source_position(position, size, new_size).round() as u16
+                        };
+                        assert_eq!(
+                            resized.pixel(x, y),
+                            original.pixel(source(x, 4, width), source(y, 4, height))
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_resize_with_bilinear_interpolation() {
+        let mut encoder = Encoder::new(4, 2, Some(&[0, 0, 0, 200, 100, 0])).unwrap();
+        encoder
+            .add_frame(&IndexedFrame::new(0, 0, 4, 2, 1))
+            .unwrap();
+        let mut frame = IndexedFrame::new(0, 0, 2, 2, 0);
+        frame.delay_time = 7;
+        encoder.add_frame(&frame).unwrap();
+        let gif = encoder.gif();
+
+        let resized = resize_gif(&gif, 8, 4, Resampling::Bilinear).unwrap();
+        let resized_images = images(&resized.gif());
+        assert_eq!(resized_images.len(), 2);
+        let (_, first) = &resized_images[0];
+        assert_eq!((first.width, first.height), (8, 4));
+        assert!((0..8).all(|x| first.pixel(x, 2) == [200, 100, 0, 255]));
+        // the edge between black and orange is blended
+        let (delay_time, second) = &resized_images[1];
+        assert_eq!(*delay_time, 7);
+        assert_eq!(second.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(second.pixel(7, 0), [200, 100, 0, 255]);
+        assert!(second.pixel(4, 0)[0] > 0 && second.pixel(4, 0)[0] < 200);
+    }
+
+    #[test]
+    fn should_keep_stray_graphic_control_extensions_with_their_images() {
+        let encoder = animation();
+        let mut gif = encoder.gif();
+        detach_graphic_control_extensions(&mut gif);
+        let delays = |gif: &GIF<'_>| -> Vec<_> {
+            images(gif)
+                .iter()
+                .map(|(delay_time, _)| *delay_time)
+                .collect()
+        };
+        let original_delays = delays(&gif);
+        assert_eq!(original_delays, vec![10, 20, 21, 22]);
+
+        // the moving square is cropped out, leaving transparent stand-ins
+        let cropped = crop_gif(&gif, 0, 0, 1, 1).unwrap();
+        let cropped_gif = cropped.gif();
+        assert_eq!(delays(&cropped_gif), original_delays);
+        assert!(!cropped_gif
+            .data
+            .iter()
+            .any(|block| matches!(block, Block::GraphicControlExtension(_))));
+
+        for &resampling in &[Resampling::Nearest, Resampling::Bilinear, Resampling::Area] {
+            let resized = resize_gif(&gif, 8, 8, resampling).unwrap();
+            let resized_gif = resized.gif();
+            assert_eq!(delays(&resized_gif), original_delays);
+            assert_eq!(
+                images(&resized_gif)[2].1.pixel(5, 3),
+                [255, 0, 0, 255],
+                "{:?}",
+                resampling
+            );
+        }
+    }
+}
@@ -0,0 +1,398 @@
+mod concat;
+mod explode;
+mod geometry;
+mod thumbnail;
+
+pub use self::concat::concat_gifs;
+pub use self::explode::{explode_gif, Explosion};
+pub use self::geometry::{crop_gif, resize_gif, Resampling};
+pub use self::thumbnail::{thumbnail_gif, PosterFrame};
+
+use super::encoder::{Encoder, EncodingError, OwnedBlock};
+use super::lzw::DecodingError;
+use super::model::{Block, GIF};
+use super::optimize::optimize_animation;
+use super::quantize::{QuantizationMethod, Quantizer};
+use super::render::{RenderedFrame, Renderer, RgbaImage};
+use super::writer::write_gif;
+use std::error::Error;
+use std::fmt;
+use std::io::{Result as IOResult, Write};
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    Decoding(DecodingError),
+    Encoding(EncodingError),
+    // A crop rectangle that is empty or exceeds the Logical Screen
+    InvalidRectangle,
+    // A Logical Screen without pixels
+    InvalidSize,
+    // A frame range that is empty or exceeds the animation
+    InvalidFrameRange,
+    // No GIF to concatenate
+    NoInput,
+    // An image without a color table, which would take on the Global Color Table of another GIF
+    MissingColorTable,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::Decoding(err) => write!(f, "cannot decode an image: {}", err),
+            EditError::Encoding(err) => write!(f, "cannot encode an image: {}", err),
+            EditError::InvalidRectangle => {
+                write!(f, "the rectangle is empty or exceeds the logical screen")
+            }
+            EditError::InvalidSize => write!(f, "the logical screen has no pixels"),
+            EditError::InvalidFrameRange => {
+                write!(f, "the frame range is empty or exceeds the animation")
+            }
+            EditError::NoInput => write!(f, "no GIF to concatenate"),
+            EditError::MissingColorTable => write!(
+                f,
+                "an image has no color table while another GIF has a global color table"
+            ),
+        }
+    }
+}
+
+impl Error for EditError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EditError::Decoding(err) => Some(err),
+            EditError::Encoding(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodingError> for EditError {
+    fn from(err: DecodingError) -> EditError {
+        EditError::Decoding(err)
+    }
+}
+
+impl From<EncodingError> for EditError {
+    fn from(err: EncodingError) -> EditError {
+        EditError::Encoding(err)
+    }
+}
+
+/// The result of an editing operation. Like `Encoder`, it owns the images it had to encode again
+/// and lends them out as a `GIF`, along with the blocks it kept from the original.
+pub struct EditedGIF<'a> {
+    // The original GIF without its blocks, with the new Logical Screen
+    header: GIF<'a>,
+    // A Global Color Table replacing the original one
+    global_color_table: Option<Vec<u8>>,
+    blocks: Vec<OwnedBlock<'a>>,
+}
+
+impl<'a> EditedGIF<'a> {
+    pub fn gif(&self) -> GIF<'_> {
+        GIF {
+            global_color_table: self
+                .global_color_table
+                .as_deref()
+                .or(self.header.global_color_table),
+            data: self.blocks.iter().map(OwnedBlock::block).collect(),
+            ..self.header.clone()
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> IOResult<()> {
+        write_gif(&self.gif(), writer)
+    }
+}
+
+/// The original GIF without its blocks.
+pub(super) fn header<'a>(gif: &GIF<'a>) -> GIF<'a> {
+    GIF {
+        data: Vec::new(),
+        ..gif.clone()
+    }
+}
+
+pub(super) fn is_image(block: &Block<'_>) -> bool {
+    matches!(block, Block::GraphicBlock { .. })
+}
+
+/// The blocks of a GIF with the Graphic Control Extension of every frame attached to its image,
+/// split into the blocks before the first image, the images, and the other blocks after it.
+pub(super) fn split_blocks<'a>(gif: &GIF<'a>) -> (Vec<Block<'a>>, Vec<Block<'a>>, Vec<Block<'a>>) {
+    let blocks: Vec<_> = gif.attached_blocks().into_iter().flatten().collect();
+    let first_image = blocks.iter().position(is_image).unwrap_or(blocks.len());
+    let (images, blocks_after) = blocks[first_image..]
+        .iter()
+        .partition(|block| is_image(block));
+    (blocks[..first_image].to_vec(), images, blocks_after)
+}
+
+/// Multiplies the delay of every frame by a factor, rounding to the nearest hundredth of a second.
+/// A factor of 2 makes the animation twice as slow, and 0.5 twice as fast.
+pub fn multiply_delays<'a>(gif: &GIF<'a>, factor: f32) -> EditedGIF<'a> {
+    let blocks = gif
+        .data
+        .iter()
+        .map(|block| {
+            let mut block = *block;
+            match &mut block {
+                Block::GraphicBlock {
+                    graphic_control_extension: Some(graphic_control_extension),
+                    ..
+                }
+                | Block::TextBlock {
+                    graphic_control_extension: Some(graphic_control_extension),
+                    ..
+                }
+                | Block::GraphicControlExtension(graphic_control_extension) => {
+                    let delay_time = f32::from(graphic_control_extension.delay_time) * factor;
+                    graphic_control_extension.delay_time =
+                        delay_time.round().clamp(0.0, f32::from(u16::MAX)) as u16;
+                }
+                _ => {}
+            }
+            OwnedBlock::Borrowed(block)
+        })
+        .collect();
+
+    EditedGIF {
+        header: header(gif),
+        global_color_table: None,
+        blocks,
+    }
+}
+
+/// Encodes composited frames of the size of the Logical Screen, as `optimize_animation` does,
+/// between the given blocks that are kept as is.
+pub(super) fn encode_composited<'a>(
+    header: GIF<'a>,
+    frames: &[(RgbaImage, u16)],
+    blocks_before: &[Block<'a>],
+    blocks_after: &[Block<'a>],
+) -> Result<EditedGIF<'a>, EditError> {
+    let images: Vec<_> = frames.iter().map(|(image, _)| image.clone()).collect();
+    let quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+    let (global_color_table, indexed_frames) = optimize_animation(&images, &quantizer, 0.0);
+
+    let encoder = Encoder::new(header.width, header.height, Some(&global_color_table))?;
+    let mut blocks: Vec<_> = blocks_before
+        .iter()
+        .map(|block| OwnedBlock::Borrowed(*block))
+        .collect();
+    for (mut frame, (_, delay_time)) in indexed_frames.into_iter().zip(frames) {
+        frame.delay_time = *delay_time;
+        blocks.push(OwnedBlock::Encoded(encoder.encode_frame(&frame)?));
+    }
+    blocks.extend(
+        blocks_after
+            .iter()
+            .map(|block| OwnedBlock::Borrowed(*block)),
+    );
+
+    let encoded = encoder.gif();
+    Ok(EditedGIF {
+        header: GIF {
+            packed_field: encoded.packed_field,
+            global_color_table: None,
+            ..header
+        },
+        global_color_table: encoded.global_color_table.map(<[u8]>::to_vec),
+        blocks,
+    })
+}
+
+pub(super) fn render(gif: &GIF<'_>) -> Result<Vec<RenderedFrame>, DecodingError> {
+    Renderer::new(gif).collect()
+}
+
+/// Builds an animation out of the given frames of a GIF, in the given order. The blocks that are
+/// not images and precede the first image stay first, while the other ones follow the last image.
+///
+/// The images are kept as is when that shows the same canvases, and otherwise the composited
+/// frames are encoded again.
+fn rearrange<'a>(gif: &GIF<'a>, order: &[usize]) -> Result<EditedGIF<'a>, EditError> {
+    let (blocks_before, images, blocks_after) = split_blocks(gif);
+    let frames: Vec<_> = gif.frames().collect();
+
+    let rendered = render(gif)?;
+    let mut rearranged = header(gif);
+    rearranged.data = blocks_before.clone();
+    rearranged
+        .data
+        .extend(order.iter().map(|&frame| images[frame]));
+    rearranged.data.extend_from_slice(&blocks_after);
+    let rearranged_rendered = render(&rearranged)?;
+    let unchanged = rearranged_rendered.len() == order.len()
+        && rearranged_rendered
+            .iter()
+            .zip(rearranged.frames())
+            .zip(order)
+            .all(|((rendered_frame, frame), &original)| {
+                rendered_frame.image == rendered[original].image
+                    && rendered_frame.delay_time == rendered[original].delay_time
+                    && frame.disposal_method() == frames[original].disposal_method()
+            });
+    if unchanged {
+        return Ok(EditedGIF {
+            header: header(gif),
+            global_color_table: None,
+            blocks: rearranged
+                .data
+                .into_iter()
+                .map(OwnedBlock::Borrowed)
+                .collect(),
+        });
+    }
+
+    let frames: Vec<_> = order
+        .iter()
+        .map(|&frame| (rendered[frame].image.clone(), rendered[frame].delay_time))
+        .collect();
+    encode_composited(header(gif), &frames, &blocks_before, &blocks_after)
+}
+
+/// Keeps a range of frames, compositing the first one again if it depends on the frames before.
+pub fn trim_gif<'a>(gif: &GIF<'a>, frames: Range<usize>) -> Result<EditedGIF<'a>, EditError> {
+    let frame_count = gif.data.iter().filter(|block| is_image(block)).count();
+    if frames.start >= frames.end || frames.end > frame_count {
+        return Err(EditError::InvalidFrameRange);
+    }
+    rearrange(gif, &frames.collect::<Vec<_>>())
+}
+
+/// Plays an animation backwards, compositing the frames again unless each one can be drawn over
+/// the next.
+pub fn reverse_gif<'a>(gif: &GIF<'a>) -> Result<EditedGIF<'a>, EditError> {
+    let frame_count = gif.data.iter().filter(|block| is_image(block)).count();
+    rearrange(gif, &(0..frame_count).rev().collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::encoder::IndexedFrame;
+    use super::super::frame::detach_graphic_control_extensions;
+    use super::super::model::DisposalMethod;
+    use super::*;
+
+    const COLORS: [u8; 9] = [0, 0, 0, 255, 0, 0, 0, 0, 255];
+
+    pub(super) fn images(gif: &GIF<'_>) -> Vec<(u16, RgbaImage)> {
+        render(gif)
+            .unwrap()
+            .into_iter()
+            .map(|frame| (frame.delay_time, frame.image))
+            .collect()
+    }
+
+    /// A red square moving over a black background, its first frame covering the whole canvas.
+    pub(super) fn animation() -> Encoder {
+        let mut encoder = Encoder::new(4, 4, Some(&COLORS)).unwrap();
+        encoder.set_loop_count(0);
+        let mut background = IndexedFrame::new(0, 0, 4, 4, 0);
+        background.delay_time = 10;
+        encoder.add_frame(&background).unwrap();
+        for (delay_time, &(left, top)) in [(1, 1), (2, 1), (2, 2)].iter().enumerate() {
+            let mut frame = IndexedFrame::new(left, top, 2, 2, 1);
+            frame.delay_time = 20 + delay_time as u16;
+            frame.disposal_method = DisposalMethod::RestoreToPrevious;
+            encoder.add_frame(&frame).unwrap();
+        }
+        encoder
+    }
+
+    #[test]
+    fn should_multiply_delays() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let delays: Vec<_> = images(&multiply_delays(&gif, 0.5).gif())
+            .iter()
+            .map(|(delay_time, _)| *delay_time)
+            .collect();
+        assert_eq!(delays, vec![5, 10, 11, 11]);
+    }
+
+    #[test]
+    fn should_trim_frames() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let original = images(&gif);
+
+        for range in [0..2, 1..3, 3..4] {
+            let trimmed = trim_gif(&gif, range.clone()).unwrap();
+            let trimmed_gif = trimmed.gif();
+            assert_eq!(images(&trimmed_gif), original[range].to_vec());
+            assert_eq!(trimmed_gif.loop_count(), Some(0));
+        }
+        assert_eq!(
+            trim_gif(&gif, 2..5).map(|_| ()),
+            Err(EditError::InvalidFrameRange)
+        );
+    }
+
+    #[test]
+    fn should_reverse_frames() {
+        let encoder = animation();
+        let gif = encoder.gif();
+        let mut original = images(&gif);
+        original.reverse();
+        let reversed = reverse_gif(&gif).unwrap();
+        assert_eq!(images(&reversed.gif()), original);
+
+        // full opaque frames are simply put in the opposite order
+        let mut encoder = Encoder::new(2, 2, Some(&COLORS)).unwrap();
+        for index in 0..3 {
+            encoder
+                .add_frame(&IndexedFrame::new(0, 0, 2, 2, index))
+                .unwrap();
+        }
+        let gif = encoder.gif();
+        let reversed = reverse_gif(&gif).unwrap();
+        let reversed_gif = reversed.gif();
+        assert_eq!(reversed_gif.global_color_table, gif.global_color_table);
+        assert_eq!(reversed_gif.data[0], gif.data[2]);
+        assert_eq!(reversed_gif.data[2], gif.data[0]);
+    }
+
+    #[test]
+    fn should_rearrange_frames_with_stray_graphic_control_extensions() {
+        let mut encoder = Encoder::new(2, 2, Some(&COLORS)).unwrap();
+        for index in 0..3 {
+            let mut frame = IndexedFrame::new(0, 0, 2, 2, index);
+            frame.delay_time = 10 * (u16::from(index) + 1);
+            encoder.add_frame(&frame).unwrap();
+        }
+        let mut gif = encoder.gif();
+        detach_graphic_control_extensions(&mut gif);
+        let original = images(&gif);
+        let delays = |gif: &GIF<'_>| -> Vec<_> {
+            images(gif)
+                .iter()
+                .map(|(delay_time, _)| *delay_time)
+                .collect()
+        };
+        assert_eq!(delays(&gif), vec![10, 20, 30]);
+
+        let reversed = reverse_gif(&gif).unwrap();
+        let mut expected = original.clone();
+        expected.reverse();
+        assert_eq!(images(&reversed.gif()), expected);
+        assert_eq!(delays(&reversed.gif()), vec![30, 20, 10]);
+
+        let trimmed = trim_gif(&gif, 1..3).unwrap();
+        assert_eq!(images(&trimmed.gif()), original[1..3].to_vec());
+        assert_eq!(delays(&trimmed.gif()), vec![20, 30]);
+    }
+
+    #[test]
+    fn should_describe_errors() {
+        let err = EditError::from(EncodingError::IndexOutOfRange(7));
+        assert_eq!(
+            err.to_string(),
+            "cannot encode an image: color index 7 is outside the color table"
+        );
+        assert!(err.source().is_some());
+        assert!(EditError::NoInput.source().is_none());
+    }
+}
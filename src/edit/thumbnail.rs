@@ -0,0 +1,197 @@
+use super::super::encoder::{Encoder, IndexedFrame, OwnedBlock};
+use super::super::model::GIF;
+use super::super::quantize::{ColorTable, QuantizationMethod, Quantizer};
+use super::super::render::{RenderedFrame, RgbaImage};
+use super::explode::index_pixels;
+use super::geometry::scale_image;
+use super::{header, render, EditError, EditedGIF, Resampling};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PosterFrame {
+    First,
+    // The frame halfway through the animation, by frame count
+    Middle,
+    // The frame whose brightness varies the most, measured by the entropy of its histogram
+    Busiest,
+}
+
+/// The Shannon entropy of the luma of opaque pixels, in bits.
+fn entropy(image: &RgbaImage) -> f32 {
+    let mut histogram = [0u32; 256];
+    let mut count = 0;
+    for pixel in image.pixels.chunks_exact(4).filter(|pixel| pixel[3] != 0) {
+        let luma =
+            0.299 * f32::from(pixel[0]) + 0.587 * f32::from(pixel[1]) + 0.114 * f32::from(pixel[2]);
+        histogram[luma.round() as usize] += 1;
+        count += 1;
+    }
+    histogram
+        .iter()
+        .filter(|&&frequency| frequency > 0)
+        .map(|&frequency| {
+            let probability = frequency as f32 / count as f32;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+fn poster_frame(frames: &[RenderedFrame], poster_frame: PosterFrame) -> &RenderedFrame {
+    match poster_frame {
+        PosterFrame::First => &frames[0],
+        PosterFrame::Middle => &frames[frames.len() / 2],
+        PosterFrame::Busiest => {
+            let entropies: Vec<_> = frames.iter().map(|frame| entropy(&frame.image)).collect();
+            let busiest = (0..frames.len()).fold(0, |busiest, frame| {
+                if entropies[frame] > entropies[busiest] {
+                    frame
+                } else {
+                    busiest
+                }
+            });
+            &frames[busiest]
+        }
+    }
+}
+
+/// Makes a still GIF out of a frame of an animation, scaled to fit within a bounding box while
+/// keeping its aspect ratio.
+///
+/// The frame is averaged down when shrinking and interpolated bilinearly when growing. The
+/// scaled pixels are then mapped to the color table of the frame's image when all of the frame's
+/// colors come from it, and quantized again otherwise.
+pub fn thumbnail_gif<'a>(
+    gif: &GIF<'a>,
+    poster: PosterFrame,
+    max_width: u16,
+    max_height: u16,
+) -> Result<EditedGIF<'a>, EditError> {
+    if max_width == 0 || max_height == 0 || gif.width == 0 || gif.height == 0 {
+        return Err(EditError::InvalidSize);
+    }
+    let frames = render(gif)?;
+    if frames.is_empty() {
+        return Err(EditError::InvalidFrameRange);
+    }
+    let frame = poster_frame(&frames, poster);
+
+    let scale = (f32::from(max_width) / f32::from(gif.width))
+        .min(f32::from(max_height) / f32::from(gif.height));
+    let size = |size: u16, max_size: u16| {
+        ((f32::from(size) * scale).round() as u16)
+            .max(1)
+            .min(max_size)
+    };
+    let (width, height) = (size(gif.width, max_width), size(gif.height, max_height));
+    let resampling = if width < gif.width || height < gif.height {
+        Resampling::Area
+    } else {
+        Resampling::Bilinear
+    };
+    let image = scale_image(&frame.image, width, height, resampling);
+
+    // the opaque colors of the image's table, if the frame only shows colors from there
+    let original_colors = gif
+        .frames()
+        .find(|image| image.block == frame.block)
+        .and_then(|image| {
+            let color_table = image.color_table?;
+            let transparent_color_index = image.transparent_color_index();
+            index_pixels(&frame.image, color_table, transparent_color_index)?;
+            Some(
+                color_table
+                    .chunks_exact(3)
+                    .enumerate()
+                    .filter(|&(index, _)| Some(index as u8) != transparent_color_index)
+                    .map(|(_, color)| [color[0], color[1], color[2]])
+                    .collect::<Vec<_>>(),
+            )
+        });
+    let quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+    let colors = quantizer.colors(&image.pixels);
+    let has_transparency = colors.iter().any(Option::is_none);
+    let quantized = match original_colors {
+        Some(original_colors) if original_colors.len() + has_transparency as usize <= 256 => {
+            let mut color_table = ColorTable::new(original_colors);
+            quantizer.map(width, &colors, &mut color_table, has_transparency)
+        }
+        _ => quantizer.quantize(width, &image.pixels),
+    };
+
+    let encoder = Encoder::new(width, height, Some(&quantized.color_table))?;
+    let image = encoder.encode_frame(&IndexedFrame {
+        indices: quantized.indices,
+        transparent_color_index: quantized.transparent_color_index,
+        ..IndexedFrame::new(0, 0, width, height, 0)
+    })?;
+    let encoded = encoder.gif();
+    let mut thumbnail = EditedGIF {
+        header: GIF {
+            width,
+            height,
+            packed_field: encoded.packed_field,
+            background_color_index: 0,
+            global_color_table: None,
+            ..header(gif)
+        },
+        global_color_table: encoded.global_color_table.map(<[u8]>::to_vec),
+        blocks: vec![OwnedBlock::Encoded(image)],
+    };
+    thumbnail.header.version = thumbnail.gif().required_version();
+    Ok(thumbnail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{animation, images};
+    use super::*;
+
+    #[test]
+    fn should_pick_poster_frames() {
+        let mut encoder = Encoder::new(2, 2, Some(&[0, 0, 0, 255, 255, 255])).unwrap();
+        encoder
+            .add_frame(&IndexedFrame::new(0, 0, 2, 2, 0))
+            .unwrap();
+        let mut checkerboard = IndexedFrame::new(0, 0, 2, 2, 0);
+        checkerboard.indices = vec![0, 1, 1, 0];
+        encoder.add_frame(&checkerboard).unwrap();
+        encoder
+            .add_frame(&IndexedFrame::new(0, 0, 2, 2, 1))
+            .unwrap();
+        let gif = encoder.gif();
+        let frames = render(&gif).unwrap();
+
+        assert_eq!(poster_frame(&frames, PosterFrame::First).block, 0);
+        assert_eq!(poster_frame(&frames, PosterFrame::Middle).block, 1);
+        assert_eq!(poster_frame(&frames, PosterFrame::Busiest).block, 1);
+        assert_eq!(entropy(&frames[0].image), 0.0);
+        assert_eq!(entropy(&frames[1].image), 1.0);
+    }
+
+    #[test]
+    fn should_scale_thumbnails_to_bounding_box() {
+        let encoder = animation();
+        let gif = encoder.gif();
+
+        let thumbnail = thumbnail_gif(&gif, PosterFrame::First, 2, 3).unwrap();
+        let still = thumbnail.gif();
+        assert_eq!((still.width, still.height), (2, 2));
+        // the original colors are kept
+        assert_eq!(still.global_color_table, gif.global_color_table);
+        let (_, image) = &images(&still)[0];
+        assert!(image
+            .pixels
+            .chunks_exact(4)
+            .all(|pixel| pixel == [0, 0, 0, 255]));
+
+        let thumbnail = thumbnail_gif(&gif, PosterFrame::Middle, 16, 8).unwrap();
+        let (_, image) = &images(&thumbnail.gif())[0];
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.pixel(5, 3), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(0, 0), [0, 0, 0, 255]);
+
+        assert_eq!(
+            thumbnail_gif(&gif, PosterFrame::First, 0, 8).map(|_| ()),
+            Err(EditError::InvalidSize)
+        );
+    }
+}
@@ -0,0 +1,707 @@
+use super::lzw::{encode, encode_lossy, minimum_code_size};
+use super::model::{
+    Block, DisposalMethod, GIFVersion, GraphicControlExtension, ImageData, ImageDescriptor,
+    SubBlocks, GIF,
+};
+use super::quantize::{ColorTable, Quantizer};
+use super::render::{interlaced_rows, RgbaImage};
+use super::writer::write_gif;
+use std::error::Error;
+use std::fmt;
+use std::io::{Result as IOResult, Write};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodingError {
+    // The number of pixels does not match the size of the image
+    InvalidPixelCount { expected: usize, actual: usize },
+    // A color table must have between 1 and 256 RGB colors
+    InvalidColorTable(usize),
+    // Neither a Local nor a Global Color Table is available for an image
+    MissingColorTable,
+    IndexOutOfRange(u8),
+    // An RGBA pixel that has no matching color in the color table
+    ColorNotInTable([u8; 4]),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::InvalidPixelCount { expected, actual } => {
+                write!(f, "expected {} pixels, got {}", expected, actual)
+            }
+            EncodingError::InvalidColorTable(colors) => write!(
+                f,
+                "invalid color table of {} colors, instead of 1 to 256 RGB triplets",
+                colors
+            ),
+            EncodingError::MissingColorTable => write!(f, "an image has no color table"),
+            EncodingError::IndexOutOfRange(index) => {
+                write!(f, "color index {} is outside the color table", index)
+            }
+            EncodingError::ColorNotInTable([r, g, b, a]) => write!(
+                f,
+                "color #{:02x}{:02x}{:02x}{:02x} is not in the color table",
+                r, g, b, a
+            ),
+        }
+    }
+}
+
+impl Error for EncodingError {}
+
+/// An image made of color indices, along with everything needed to display it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedFrame {
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    // In row order, regardless of `interlaced`
+    pub indices: Vec<u8>,
+    // RGB triplets
+    pub local_color_table: Option<Vec<u8>>,
+    pub delay_time: u16,
+    pub disposal_method: DisposalMethod,
+    pub transparent_color_index: Option<u8>,
+    pub interlaced: bool,
+}
+
+impl IndexedFrame {
+    /// A frame covering the given area with a single color index.
+    pub fn new(left: u16, top: u16, width: u16, height: u16, index: u8) -> IndexedFrame {
+        IndexedFrame {
+            left,
+            top,
+            width,
+            height,
+            indices: vec![index; width as usize * height as usize],
+            local_color_table: None,
+            delay_time: 0,
+            disposal_method: DisposalMethod::Unspecified,
+            transparent_color_index: None,
+            interlaced: false,
+        }
+    }
+
+    /// Whether the frame has a delay, a disposal method or a transparent color, which take a
+    /// Graphic Control Extension.
+    pub(crate) fn needs_graphic_control_extension(&self) -> bool {
+        self.delay_time != 0
+            || self.disposal_method != DisposalMethod::Unspecified
+            || self.transparent_color_index.is_some()
+    }
+
+    /// A frame covering the given area with RGBA pixels in row order, quantized into a Local
+    /// Color Table.
+    pub fn from_rgba(
+        left: u16,
+        top: u16,
+        width: u16,
+        height: u16,
+        pixels: &[u8],
+        quantizer: &Quantizer,
+    ) -> Result<IndexedFrame, EncodingError> {
+        let expected = width as usize * height as usize;
+        if pixels.len() != 4 * expected {
+            return Err(EncodingError::InvalidPixelCount {
+                expected,
+                actual: pixels.len() / 4,
+            });
+        }
+
+        let quantized = quantizer.quantize(width, pixels);
+        Ok(IndexedFrame {
+            indices: quantized.indices,
+            local_color_table: Some(quantized.color_table),
+            transparent_color_index: quantized.transparent_color_index,
+            ..IndexedFrame::new(left, top, width, height, 0)
+        })
+    }
+}
+
+/// Quantizes the images of an animation with a shared color table, returned as RGB triplets for
+/// the Global Color Table. Only the frames whose average error with the shared table exceeds
+/// `max_error`, a distance in Oklab where 0.02 is about the smallest noticeable difference, get a
+/// Local Color Table of their own.
+///
+/// The frames are placed at the top left corner of the Logical Screen.
+pub fn quantize_animation(
+    images: &[RgbaImage],
+    quantizer: &Quantizer,
+    max_error: f32,
+) -> (Vec<u8>, Vec<IndexedFrame>) {
+    let colors: Vec<_> = images
+        .iter()
+        .map(|image| quantizer.colors(&image.pixels))
+        .collect();
+    let all_colors = colors.concat();
+    let mut global_color_table = quantizer.color_table(&all_colors);
+    let has_transparency = all_colors.iter().any(Option::is_none);
+
+    let frames = images
+        .iter()
+        .zip(&colors)
+        .map(|(image, colors)| {
+            let frame = IndexedFrame::new(0, 0, image.width, image.height, 0);
+            if global_color_table.mean_error(colors) <= max_error {
+                let quantized = quantizer.map(image.width, colors, &mut global_color_table, false);
+                IndexedFrame {
+                    indices: quantized.indices,
+                    transparent_color_index: quantized.transparent_color_index,
+                    ..frame
+                }
+            } else {
+                let mut local_color_table = quantizer.color_table(colors);
+                let has_transparency = colors.iter().any(Option::is_none);
+                let quantized = quantizer.map(
+                    image.width,
+                    colors,
+                    &mut local_color_table,
+                    has_transparency,
+                );
+                IndexedFrame {
+                    indices: quantized.indices,
+                    local_color_table: Some(quantized.color_table),
+                    transparent_color_index: quantized.transparent_color_index,
+                    ..frame
+                }
+            }
+        })
+        .collect();
+
+    let mut global_color_table = global_color_table.colors().to_vec();
+    if has_transparency {
+        global_color_table.push([0, 0, 0]);
+    }
+    (
+        global_color_table
+            .iter()
+            .flat_map(|color| color.iter().cloned())
+            .collect(),
+        frames,
+    )
+}
+
+/// The packed field bits encoding the size of a color table, which is padded to the next power
+/// of two.
+pub(crate) fn color_table_size_bits(color_table: &[u8]) -> Result<u8, EncodingError> {
+    let colors = color_table.len() / 3;
+    if !color_table.len().is_multiple_of(3) || colors == 0 || colors > 256 {
+        return Err(EncodingError::InvalidColorTable(colors));
+    }
+
+    let mut bits = 0;
+    while (2 << bits) < colors {
+        bits += 1;
+    }
+    Ok(bits)
+}
+
+fn padded_color_table(color_table: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    let size_bits = color_table_size_bits(color_table)?;
+    let mut padded = color_table.to_vec();
+    padded.resize(3 * (2 << size_bits), 0);
+    Ok(padded)
+}
+
+/// A Table-Based Image whose data is owned by the encoder.
+pub(crate) struct EncodedImage {
+    pub(crate) graphic_control_extension: Option<GraphicControlExtension>,
+    pub(crate) image_descriptor: ImageDescriptor,
+    pub(crate) local_color_table: Option<Vec<u8>>,
+    pub(crate) lzw_minimum_code_size: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+impl EncodedImage {
+    pub(crate) fn block(&self) -> Block<'_> {
+        Block::GraphicBlock {
+            graphic_control_extension: self.graphic_control_extension,
+            image_descriptor: self.image_descriptor,
+            local_color_table: self.local_color_table.as_deref(),
+            image_data: ImageData {
+                lzw_minimum_code_size: self.lzw_minimum_code_size,
+                data: SubBlocks(&self.data),
+            },
+        }
+    }
+}
+
+/// A block of a GIF derived from another one, either borrowed from it or with a new image.
+pub(crate) enum OwnedBlock<'a> {
+    Borrowed(Block<'a>),
+    Encoded(EncodedImage),
+}
+
+impl<'a> OwnedBlock<'a> {
+    pub(crate) fn block(&self) -> Block<'_> {
+        match self {
+            OwnedBlock::Borrowed(block) => *block,
+            OwnedBlock::Encoded(image) => image.block(),
+        }
+    }
+}
+
+/// Builds a GIF out of indexed frames, compressing their image data.
+///
+/// The encoder owns all the encoded data and lends it out as a `GIF`, which can be inspected or
+/// written with `write_gif`.
+pub struct Encoder {
+    width: u16,
+    height: u16,
+    global_color_table: Option<Vec<u8>>,
+    global_color_table_size: usize,
+    background_color_index: u8,
+    // The NETSCAPE2.0 application extension data
+    loop_extension: Option<Vec<u8>>,
+    // The largest perceptual error allowed when compressing images, 0 being lossless
+    lossy_tolerance: f32,
+    images: Vec<EncodedImage>,
+}
+
+impl Encoder {
+    /// Creates an encoder for a Logical Screen of the given size. The Global Color Table, made of
+    /// RGB triplets, is padded with black up to the next power of two.
+    pub fn new(
+        width: u16,
+        height: u16,
+        global_color_table: Option<&[u8]>,
+    ) -> Result<Encoder, EncodingError> {
+        let padded = match global_color_table {
+            Some(global_color_table) => Some(padded_color_table(global_color_table)?),
+            None => None,
+        };
+
+        Ok(Encoder {
+            width,
+            height,
+            global_color_table: padded,
+            global_color_table_size: global_color_table.map_or(0, |table| table.len() / 3),
+            background_color_index: 0,
+            loop_extension: None,
+            lossy_tolerance: 0.0,
+            images: Vec::new(),
+        })
+    }
+
+    pub fn set_background_color_index(&mut self, background_color_index: u8) {
+        self.background_color_index = background_color_index;
+    }
+
+    /// Makes the animation repeat the given number of times, where 0 means forever.
+    pub fn set_loop_count(&mut self, loop_count: u16) {
+        let [low, high] = loop_count.to_le_bytes();
+        let mut loop_extension = vec![0x0b];
+        loop_extension.extend_from_slice(b"NETSCAPE2.0");
+        loop_extension.extend_from_slice(&[0x03, 0x01, low, high, 0x00]);
+        self.loop_extension = Some(loop_extension);
+    }
+
+    /// Makes the LZW compression of the following frames lossy, letting it swap a pixel's color for
+    /// another one at most `tolerance` away in Oklab, where 0.02 is about the smallest noticeable
+    /// difference, whenever that makes for longer strings. Transparent pixels are kept as is.
+    pub fn set_lossy_tolerance(&mut self, tolerance: f32) {
+        self.lossy_tolerance = tolerance;
+    }
+
+    /// Compresses a frame and appends it to the GIF. A Graphic Control Extension is only added
+    /// when the frame has a delay, a disposal method or a transparent color.
+    pub fn add_frame(&mut self, frame: &IndexedFrame) -> Result<(), EncodingError> {
+        let image = self.encode_frame(frame)?;
+        self.images.push(image);
+        Ok(())
+    }
+
+    /// Compresses a frame as `add_frame` does, without adding it.
+    pub(crate) fn encode_frame(&self, frame: &IndexedFrame) -> Result<EncodedImage, EncodingError> {
+        let width = frame.width as usize;
+        let height = frame.height as usize;
+        if frame.indices.len() != width * height {
+            return Err(EncodingError::InvalidPixelCount {
+                expected: width * height,
+                actual: frame.indices.len(),
+            });
+        }
+
+        let (colors, local_color_table, local_size_bits) = match &frame.local_color_table {
+            Some(local_color_table) => (
+                local_color_table.len() / 3,
+                Some(padded_color_table(local_color_table)?),
+                color_table_size_bits(local_color_table)?,
+            ),
+            None if self.global_color_table.is_some() => (self.global_color_table_size, None, 0),
+            None => return Err(EncodingError::MissingColorTable),
+        };
+        if let Some(&index) = frame
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= colors)
+        {
+            return Err(EncodingError::IndexOutOfRange(index));
+        }
+
+        let graphic_control_extension = if frame.needs_graphic_control_extension() {
+            Some(GraphicControlExtension::new(
+                frame.disposal_method,
+                frame.delay_time,
+                frame.transparent_color_index,
+            ))
+        } else {
+            None
+        };
+
+        let mut packed_field = 0;
+        if local_color_table.is_some() {
+            packed_field |= 0b_1000_0000 | local_size_bits;
+        }
+        if frame.interlaced {
+            packed_field |= 0b_0100_0000;
+        }
+
+        let lzw_minimum_code_size = minimum_code_size(colors);
+        let interlaced;
+        let indices = if frame.interlaced {
+            interlaced = interlaced_rows(height)
+                .flat_map(|y| &frame.indices[y * width..(y + 1) * width])
+                .cloned()
+                .collect::<Vec<_>>();
+            &interlaced
+        } else {
+            &frame.indices
+        };
+        let data = if self.lossy_tolerance > 0.0 {
+            let color_table = frame
+                .local_color_table
+                .as_deref()
+                .or(self.global_color_table.as_deref())
+                .unwrap_or(&[]);
+            let color_table = ColorTable::new(
+                color_table
+                    .chunks_exact(3)
+                    .map(|color| [color[0], color[1], color[2]])
+                    .collect(),
+            );
+            let transparent_color_index = frame.transparent_color_index;
+            encode_lossy(
+                lzw_minimum_code_size,
+                indices,
+                self.lossy_tolerance,
+                |a, b| {
+                    // substitutes may go up to the padded size of the table
+                    if a as usize >= colors
+                        || b as usize >= colors
+                        || Some(a) == transparent_color_index
+                        || Some(b) == transparent_color_index
+                    {
+                        f32::INFINITY
+                    } else {
+                        color_table.distance(a, b)
+                    }
+                },
+            )
+        } else {
+            encode(lzw_minimum_code_size, indices)
+        };
+
+        Ok(EncodedImage {
+            graphic_control_extension,
+            image_descriptor: ImageDescriptor {
+                left: frame.left,
+                top: frame.top,
+                width: frame.width,
+                height: frame.height,
+                packed_field,
+            },
+            local_color_table,
+            lzw_minimum_code_size,
+            data,
+        })
+    }
+
+    /// The GIF made of all the frames added so far.
+    pub fn gif(&self) -> GIF<'_> {
+        let mut data = Vec::with_capacity(self.images.len() + 1);
+        if let Some(loop_extension) = &self.loop_extension {
+            data.push(Block::ApplicationExtension(SubBlocks(loop_extension)));
+        }
+        data.extend(self.images.iter().map(EncodedImage::block));
+
+        let mut gif = GIF {
+            version: GIFVersion::GIF89a,
+            width: self.width,
+            height: self.height,
+            // color resolution of 8 bits
+            packed_field: 0b_0111_0000,
+            background_color_index: self.background_color_index,
+            pixel_aspect_ratio: 0,
+            global_color_table: self.global_color_table.as_deref(),
+            data,
+            trailer: true,
+        };
+        if let Some(global_color_table) = &self.global_color_table {
+            // the padded table always has a valid size
+            gif.packed_field |=
+                0b_1000_0000 | color_table_size_bits(global_color_table).unwrap_or(0);
+        }
+        gif.version = gif.required_version();
+        gif
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> IOResult<()> {
+        write_gif(&self.gif(), writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::quantize::QuantizationMethod;
+    use super::super::render::{decode_indices, Renderer};
+    use super::*;
+
+    const COLORS: [u8; 9] = [0, 0, 0, 255, 0, 0, 0, 0, 255];
+
+    #[test]
+    fn should_pad_color_tables() {
+        assert_eq!(color_table_size_bits(&COLORS[..3]), Ok(0));
+        assert_eq!(color_table_size_bits(&COLORS), Ok(1));
+        assert_eq!(
+            color_table_size_bits(&[]),
+            Err(EncodingError::InvalidColorTable(0))
+        );
+        assert_eq!(
+            padded_color_table(&COLORS),
+            Ok(vec![0, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn should_encode_still_image_as_gif87a() {
+        let mut encoder = Encoder::new(2, 2, Some(&COLORS)).unwrap();
+        let mut frame = IndexedFrame::new(0, 0, 2, 2, 0);
+        frame.indices = vec![0, 1, 2, 1];
+        encoder.add_frame(&frame).unwrap();
+
+        let gif = encoder.gif();
+        assert_eq!(gif.version, GIFVersion::GIF87a);
+        assert_eq!(gif.packed_field, 0xf1);
+        assert_eq!(gif.global_color_table.unwrap().len(), 12);
+
+        let frames: Vec<_> = Renderer::new(&gif).map(Result::unwrap).collect();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            frames[0].image.pixels,
+            vec![0, 0, 0, 255, 255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn should_encode_animation() {
+        let mut encoder = Encoder::new(4, 4, Some(&COLORS)).unwrap();
+        encoder.set_loop_count(0);
+
+        let mut first = IndexedFrame::new(0, 0, 4, 4, 1);
+        first.delay_time = 50;
+        first.interlaced = true;
+        encoder.add_frame(&first).unwrap();
+
+        let mut second = IndexedFrame::new(1, 1, 2, 2, 0);
+        second.local_color_table = Some(vec![0, 255, 0]);
+        second.disposal_method = DisposalMethod::RestoreToBackgroundColor;
+        encoder.add_frame(&second).unwrap();
+
+        let gif = encoder.gif();
+        assert_eq!(gif.version, GIFVersion::GIF89a);
+        assert_eq!(gif.loop_count(), Some(0));
+
+        let frames: Vec<_> = Renderer::new(&gif).map(Result::unwrap).collect();
+        assert_eq!(frames[0].delay_time, 50);
+        assert_eq!(frames[0].image.pixel(1, 1), [255, 0, 0, 255]);
+        assert_eq!(frames[1].image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(frames[1].image.pixel(1, 1), [0, 255, 0, 255]);
+
+        let mut written = Vec::new();
+        encoder.write(&mut written).unwrap();
+        assert_eq!(super::super::parser::parse_gif(&written), Ok(gif));
+    }
+
+    #[test]
+    fn should_encode_quantized_rgba_frame() {
+        let pixels = [255, 0, 0, 255, 0, 0, 0, 0, 0, 0, 255, 255, 255, 0, 0, 255];
+        let quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+        let frame = IndexedFrame::from_rgba(0, 0, 2, 2, &pixels, &quantizer).unwrap();
+        assert_eq!(frame.transparent_color_index, Some(2));
+
+        let mut encoder = Encoder::new(2, 2, None).unwrap();
+        encoder.add_frame(&frame).unwrap();
+        let gif = encoder.gif();
+        let frames: Vec<_> = Renderer::new(&gif).map(Result::unwrap).collect();
+        assert_eq!(&frames[0].image.pixels[..], &pixels[..]);
+
+        assert_eq!(
+            IndexedFrame::from_rgba(0, 0, 2, 2, &pixels[..12], &quantizer),
+            Err(EncodingError::InvalidPixelCount {
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn should_share_global_color_table() {
+        let image = |colors: &[[u8; 4]]| RgbaImage {
+            width: 2,
+            height: 2,
+            pixels: colors
+                .iter()
+                .flat_map(|color| color.iter().cloned())
+                .collect(),
+        };
+        let red = [255, 0, 0, 255];
+        let images = [
+            image(&[red; 4]),
+            image(&[red; 4]),
+            image(&[
+                [0, 255, 0, 255],
+                [0, 0, 255, 255],
+                [0, 0, 0, 0],
+                [0, 0, 0, 0],
+            ]),
+        ];
+        let mut quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+        quantizer.max_colors = 3;
+
+        // green and blue share an entry of the Global Color Table
+        let (global_color_table, frames) = quantize_animation(&images, &quantizer, 0.01);
+        assert_eq!(global_color_table, vec![0, 128, 128, 255, 0, 0, 0, 0, 0]);
+        assert_eq!(frames[0].local_color_table, None);
+        assert_eq!(frames[1].local_color_table, None);
+        assert_eq!(frames[1].indices, vec![1; 4]);
+        assert_eq!(
+            frames[2].local_color_table,
+            Some(vec![0, 0, 255, 0, 255, 0, 0, 0, 0])
+        );
+        assert_eq!(frames[2].transparent_color_index, Some(2));
+
+        let mut encoder = Encoder::new(2, 2, Some(&global_color_table)).unwrap();
+        for frame in &frames {
+            encoder.add_frame(frame).unwrap();
+        }
+        let gif = encoder.gif();
+        let rendered: Vec<_> = Renderer::new(&gif).map(Result::unwrap).collect();
+        assert_eq!(rendered[1].image.pixels, images[1].pixels);
+        assert_eq!(rendered[2].image.pixel(0, 0), [0, 255, 0, 255]);
+
+        let (_, frames) = quantize_animation(&images, &quantizer, 1.0);
+        assert!(frames.iter().all(|frame| frame.local_color_table.is_none()));
+        assert_eq!(frames[0].transparent_color_index, None);
+        assert_eq!(frames[2].transparent_color_index, Some(2));
+    }
+
+    #[test]
+    fn should_bound_lossy_compression_error() {
+        // a gradient dithered with 32 colors, which LZW compresses poorly
+        let mut pixels = Vec::new();
+        for y in 0..128u8 {
+            for x in 0..128u8 {
+                pixels.extend_from_slice(&[x * 2, y, 255 - y * 2, 255]);
+            }
+        }
+        let mut quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+        quantizer.max_colors = 32;
+        let frame = IndexedFrame::from_rgba(0, 0, 128, 128, &pixels, &quantizer).unwrap();
+        let color_table = frame.local_color_table.clone().unwrap();
+
+        let encode = |tolerance| {
+            let mut encoder = Encoder::new(128, 128, None).unwrap();
+            encoder.set_lossy_tolerance(tolerance);
+            encoder.add_frame(&frame).unwrap();
+            let mut gif_data = Vec::new();
+            encoder.write(&mut gif_data).unwrap();
+            let pixels = Renderer::new(&encoder.gif())
+                .next()
+                .unwrap()
+                .unwrap()
+                .image
+                .pixels;
+            (gif_data.len(), pixels)
+        };
+        let (lossless_size, lossless_pixels) = encode(0.0);
+        let (lossy_size, lossy_pixels) = encode(0.1);
+        assert!(
+            lossy_size * 3 < lossless_size * 2,
+            "{} {}",
+            lossy_size,
+            lossless_size
+        );
+
+        let mut color_table = ColorTable::new(
+            color_table
+                .chunks_exact(3)
+                .map(|color| [color[0], color[1], color[2]])
+                .collect(),
+        );
+        for (lossless, lossy) in lossless_pixels
+            .chunks_exact(4)
+            .zip(lossy_pixels.chunks_exact(4))
+        {
+            let lossless = color_table.nearest([lossless[0], lossless[1], lossless[2]]);
+            let lossy = color_table.nearest([lossy[0], lossy[1], lossy[2]]);
+            assert!(color_table.distance(lossless, lossy) <= 0.1);
+        }
+    }
+
+    #[test]
+    fn should_only_substitute_colors_of_the_table() {
+        let mut frame = IndexedFrame::new(0, 0, 16, 16, 0);
+        for (i, index) in frame.indices.iter_mut().enumerate() {
+            *index = (i % 7 % 3) as u8;
+        }
+        let decoded_indices = |encoder: &Encoder| match encoder.gif().data.last() {
+            Some(Block::GraphicBlock {
+                image_descriptor,
+                image_data,
+                ..
+            }) => decode_indices(image_descriptor, image_data).unwrap(),
+            _ => unreachable!(),
+        };
+
+        // a local color table of 3 colors, padded to 4
+        let mut local_frame = frame.clone();
+        local_frame.local_color_table = Some(COLORS.to_vec());
+        let mut encoder = Encoder::new(16, 16, None).unwrap();
+        encoder.set_lossy_tolerance(0.5);
+        encoder.add_frame(&local_frame).unwrap();
+        assert!(decoded_indices(&encoder).iter().all(|&index| index < 3));
+
+        // no local color table, the global one being padded as well
+        let mut encoder = Encoder::new(16, 16, Some(&COLORS)).unwrap();
+        encoder.set_lossy_tolerance(0.5);
+        encoder.add_frame(&frame).unwrap();
+        assert!(decoded_indices(&encoder).iter().all(|&index| index < 3));
+    }
+
+    #[test]
+    fn should_reject_invalid_frames() {
+        let mut encoder = Encoder::new(2, 2, None).unwrap();
+        assert_eq!(
+            encoder.add_frame(&IndexedFrame::new(0, 0, 2, 2, 0)),
+            Err(EncodingError::MissingColorTable)
+        );
+
+        let mut encoder = Encoder::new(2, 2, Some(&COLORS)).unwrap();
+        assert_eq!(
+            encoder.add_frame(&IndexedFrame::new(0, 0, 2, 2, 3)),
+            Err(EncodingError::IndexOutOfRange(3))
+        );
+
+        let mut frame = IndexedFrame::new(0, 0, 2, 2, 0);
+        frame.indices.pop();
+        assert_eq!(
+            encoder.add_frame(&frame),
+            Err(EncodingError::InvalidPixelCount {
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+}
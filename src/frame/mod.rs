@@ -0,0 +1,281 @@
+use super::lzw::DecodingError;
+use super::model::{
+    Block, DisposalMethod, GraphicControlExtension, ImageData, ImageDescriptor, GIF,
+};
+use super::render::{decode_image, decode_indices, RgbaImage};
+
+/// An image of a GIF along with everything needed to draw it, borrowed from the GIF.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    // Index of the frame's graphic block in `GIF::data`
+    pub block: usize,
+    // The Graphic Control Extension of the image, or else a preceding stray one
+    pub graphic_control_extension: Option<GraphicControlExtension>,
+    pub image_descriptor: ImageDescriptor,
+    // The Local Color Table, or else the Global Color Table
+    pub color_table: Option<&'a [u8]>,
+    pub image_data: ImageData<'a>,
+}
+
+impl<'a> Frame<'a> {
+    pub fn disposal_method(&self) -> DisposalMethod {
+        self.graphic_control_extension
+            .map_or(DisposalMethod::Unspecified, |graphic_control_extension| {
+                graphic_control_extension.disposal_method()
+            })
+    }
+
+    /// The delay before the next frame, in hundredths of a second.
+    pub fn delay_time(&self) -> u16 {
+        self.graphic_control_extension
+            .map_or(0, |graphic_control_extension| {
+                graphic_control_extension.delay_time
+            })
+    }
+
+    pub fn transparent_color_index(&self) -> Option<u8> {
+        self.graphic_control_extension
+            .filter(|graphic_control_extension| graphic_control_extension.transparent_color_flag())
+            .map(|graphic_control_extension| graphic_control_extension.transparent_color_index)
+    }
+
+    /// Decompresses the color indices of the image in row order.
+    pub fn indices(&self) -> Result<Vec<u8>, DecodingError> {
+        decode_indices(&self.image_descriptor, &self.image_data)
+    }
+
+    /// Decompresses the image on its own, without compositing it.
+    pub fn image(&self) -> Result<RgbaImage, DecodingError> {
+        decode_image(
+            self.graphic_control_extension.as_ref(),
+            &self.image_descriptor,
+            self.color_table,
+            &self.image_data,
+        )
+    }
+}
+
+impl<'a> GIF<'a> {
+    /// The images of the GIF in order, skipping the Plain Text Extensions and the other
+    /// extensions in between.
+    ///
+    /// Like in browsers, an image is controlled by the last Graphic Control Extension since the
+    /// previous graphic rendering block, even when other extensions come in between.
+    pub fn frames(&self) -> impl Iterator<Item = Frame<'a>> + '_ {
+        let mut pending_graphic_control_extension = None;
+        self.data
+            .iter()
+            .enumerate()
+            .filter_map(move |(block, data)| match *data {
+                Block::GraphicControlExtension(graphic_control_extension) => {
+                    pending_graphic_control_extension = Some(graphic_control_extension);
+                    None
+                }
+                Block::GraphicBlock {
+                    graphic_control_extension,
+                    image_descriptor,
+                    local_color_table,
+                    image_data,
+                } => Some(Frame {
+                    block,
+                    graphic_control_extension: graphic_control_extension
+                        .or(pending_graphic_control_extension.take()),
+                    image_descriptor,
+                    color_table: local_color_table.or(self.global_color_table),
+                    image_data,
+                }),
+                Block::TextBlock { .. } => {
+                    pending_graphic_control_extension = None;
+                    None
+                }
+                _ => None,
+            })
+    }
+
+    /// Whether each block is a stray Graphic Control Extension before an image, with no Plain
+    /// Text Extension in between. Once the Graphic Control Extension of each frame is attached to
+    /// its image, these blocks no longer change anything.
+    fn detached_graphic_control_extensions(&self) -> Vec<bool> {
+        let mut detached = vec![false; self.data.len()];
+        let mut before_image = false;
+        for (index, block) in self.data.iter().enumerate().rev() {
+            match block {
+                Block::GraphicControlExtension(_) => detached[index] = before_image,
+                Block::GraphicBlock { .. } => before_image = true,
+                Block::TextBlock { .. } => before_image = false,
+                _ => {}
+            }
+        }
+        detached
+    }
+
+    /// The blocks with the Graphic Control Extension of every frame attached to its image, the
+    /// stray Graphic Control Extensions that then no longer change anything being `None`.
+    pub(crate) fn attached_blocks(&self) -> Vec<Option<Block<'a>>> {
+        let detached = self.detached_graphic_control_extensions();
+        let mut frames = self.frames();
+        self.data
+            .iter()
+            .zip(detached)
+            .map(|(block, detached)| {
+                let mut block = *block;
+                if let Block::GraphicBlock {
+                    graphic_control_extension,
+                    ..
+                } = &mut block
+                {
+                    *graphic_control_extension = frames
+                        .next()
+                        .and_then(|frame| frame.graphic_control_extension);
+                }
+                Some(block).filter(|_| !detached)
+            })
+            .collect()
+    }
+}
+
+/// Moves the Graphic Control Extension of every image into a stray block before it, followed by
+/// a comment, as some encoders write them.
+#[cfg(test)]
+pub(crate) fn detach_graphic_control_extensions(gif: &mut GIF<'_>) {
+    let mut data = Vec::with_capacity(gif.data.len());
+    for block in &gif.data {
+        match *block {
+            Block::GraphicBlock {
+                graphic_control_extension: Some(graphic_control_extension),
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } => {
+                data.push(Block::GraphicControlExtension(graphic_control_extension));
+                data.push(Block::CommentExtension(super::model::SubBlocks(&[
+                    1, b'a', 0,
+                ])));
+                data.push(Block::GraphicBlock {
+                    graphic_control_extension: None,
+                    image_descriptor,
+                    local_color_table,
+                    image_data,
+                });
+            }
+            block => data.push(block),
+        }
+    }
+    gif.data = data;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::model::{GIFVersion, SubBlocks};
+    use super::*;
+
+    const GLOBAL_COLOR_TABLE: [u8; 6] = [0, 0, 0, 255, 255, 255];
+    const LOCAL_COLOR_TABLE: [u8; 6] = [255, 0, 0, 0, 0, 255];
+
+    fn image(
+        graphic_control_extension: Option<GraphicControlExtension>,
+        local_color_table: Option<&'static [u8]>,
+    ) -> Block<'static> {
+        Block::GraphicBlock {
+            graphic_control_extension,
+            image_descriptor: ImageDescriptor {
+                left: 0,
+                top: 0,
+                width: 1,
+                height: 1,
+                packed_field: if local_color_table.is_some() {
+                    0b_1000_0000
+                } else {
+                    0
+                },
+            },
+            local_color_table,
+            image_data: ImageData {
+                lzw_minimum_code_size: 2,
+                data: SubBlocks(&[0x02, 0x44, 0x01, 0x00]),
+            },
+        }
+    }
+
+    #[test]
+    fn should_view_frames() {
+        let graphic_control_extension =
+            GraphicControlExtension::new(DisposalMethod::RestoreToPrevious, 7, Some(1));
+        let gif = GIF {
+            version: GIFVersion::GIF89a,
+            width: 1,
+            height: 1,
+            packed_field: 0b_1000_0000,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: Some(&GLOBAL_COLOR_TABLE),
+            data: vec![
+                Block::GraphicControlExtension(GraphicControlExtension::new(
+                    DisposalMethod::Unspecified,
+                    3,
+                    None,
+                )),
+                Block::GraphicControlExtension(GraphicControlExtension::new(
+                    DisposalMethod::DoNotDispose,
+                    5,
+                    None,
+                )),
+                Block::CommentExtension(SubBlocks(&[0x00])),
+                image(None, None),
+                Block::TextBlock {
+                    graphic_control_extension: None,
+                    text: SubBlocks(&[0x00]),
+                },
+                image(Some(graphic_control_extension), Some(&LOCAL_COLOR_TABLE)),
+            ],
+            trailer: true,
+        };
+
+        let frames: Vec<_> = gif.frames().collect();
+        assert_eq!(frames.len(), 2);
+        let (first, second) = (frames[0], frames[1]);
+
+        // the last stray Graphic Control Extension applies
+        assert_eq!(first.block, 3);
+        assert_eq!(first.color_table, Some(&GLOBAL_COLOR_TABLE[..]));
+        assert_eq!(first.disposal_method(), DisposalMethod::DoNotDispose);
+        assert_eq!(first.delay_time(), 5);
+        assert_eq!(first.transparent_color_index(), None);
+        assert_eq!(first.indices(), Ok(vec![0]));
+        assert_eq!(first.image().unwrap().pixels, vec![0, 0, 0, 255]);
+
+        assert_eq!(second.block, 5);
+        assert_eq!(second.color_table, Some(&LOCAL_COLOR_TABLE[..]));
+        assert_eq!(second.disposal_method(), DisposalMethod::RestoreToPrevious);
+        assert_eq!(second.delay_time(), 7);
+        assert_eq!(second.transparent_color_index(), Some(1));
+        assert_eq!(second.image().unwrap().pixels, vec![255, 0, 0, 255]);
+
+        assert_eq!(
+            gif.detached_graphic_control_extensions(),
+            vec![true, true, false, false, false, false]
+        );
+        let attached = gif.attached_blocks();
+        assert_eq!(attached[..3], [None, None, Some(gif.data[2])]);
+        assert_eq!(
+            attached[3],
+            Some(image(
+                Some(GraphicControlExtension::new(
+                    DisposalMethod::DoNotDispose,
+                    5,
+                    None
+                )),
+                None
+            ))
+        );
+        assert_eq!(attached[5], Some(gif.data[5]));
+
+        // a Graphic Control Extension before a Plain Text Extension is not detached
+        let mut gif = gif;
+        gif.data.swap(3, 4);
+        assert_eq!(
+            gif.detached_graphic_control_extensions(),
+            vec![false, false, false, false, false, false]
+        );
+    }
+}
@@ -1,10 +1,49 @@
-#[macro_use]
-extern crate nom;
-
+#[cfg(feature = "image")]
+mod decoder;
+mod description;
+mod edit;
+mod encoder;
+mod frame;
+mod lzw;
 mod model;
+mod optimize;
 mod parser;
+mod quantize;
+mod render;
+mod timing;
+mod transform;
+mod validator;
+mod writer;
 
+#[cfg(feature = "image")]
+pub use self::decoder::GIFDecoder;
+pub use self::description::{FrameDescription, GIFDescription, Pixels};
+pub use self::edit::{
+    concat_gifs, crop_gif, explode_gif, multiply_delays, resize_gif, reverse_gif, thumbnail_gif,
+    trim_gif, EditError, EditedGIF, Explosion, PosterFrame, Resampling,
+};
+pub use self::encoder::{quantize_animation, Encoder, EncodingError, IndexedFrame};
+pub use self::frame::Frame;
+pub use self::lzw::{trace_lzw, DecodingError, LZWCode};
 pub use self::model::{
-    Block, GIFVersion, GraphicControlExtension, ImageData, ImageDescriptor, SubBlocks, GIF,
+    Block, DisposalMethod, GIFVersion, GraphicControlExtension, ImageData, ImageDescriptor,
+    SubBlocks, GIF,
+};
+#[cfg(feature = "serde")]
+pub use self::model::{DeserializedBlock, DeserializedGIF};
+pub use self::optimize::{
+    crop_frames, make_unchanged_pixels_transparent, optimize_animation, CroppedFrame,
+    GIFOptimizer, OptimizedGIF,
+};
+pub use self::parser::{
+    parse_block, parse_gif, parse_gif_with_ranges, parse_header, ParsedGIF, ParsingError,
+    ParsingErrorKind,
+};
+pub use self::quantize::{Dithering, QuantizationMethod, QuantizedImage, Quantizer};
+pub use self::render::{
+    decode_image, decode_indices, indices_to_rgba, RenderedFrame, Renderer, RgbaImage,
 };
-pub use self::parser::parse_gif;
+pub use self::timing::DelayPolicy;
+pub use self::transform::{downgrade_to_gif87a, Extension, StrippedExtension};
+pub use self::validator::{validate, Diagnostic, Location, Severity, Violation};
+pub use self::writer::write_gif;
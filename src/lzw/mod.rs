@@ -0,0 +1,626 @@
+use super::model::SubBlocks;
+use std::error::Error;
+use std::fmt;
+
+/// The largest code size allowed by the GIF variant of LZW.
+///
+/// See the GIF89a spec Appendix F
+const MAX_CODE_SIZE: u8 = 12;
+const MAX_CODES: usize = 1 << MAX_CODE_SIZE;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodingError {
+    InvalidMinimumCodeSize(u8),
+    InvalidCode(u16),
+}
+
+impl fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodingError::InvalidMinimumCodeSize(size) => {
+                write!(f, "invalid LZW minimum code size {}", size)
+            }
+            DecodingError::InvalidCode(code) => write!(f, "invalid LZW code {}", code),
+        }
+    }
+}
+
+impl Error for DecodingError {}
+
+/// Reads variable-length codes, least significant bit first.
+struct CodeReader<I> {
+    bytes: I,
+    buffer: u32,
+    buffered_bits: u8,
+}
+
+impl<I: Iterator<Item = u8>> CodeReader<I> {
+    fn read(&mut self, code_size: u8) -> Option<u16> {
+        while self.buffered_bits < code_size {
+            self.buffer |= u32::from(self.bytes.next()?) << self.buffered_bits;
+            self.buffered_bits += 8;
+        }
+        let code = (self.buffer & ((1 << code_size) - 1)) as u16;
+        self.buffer >>= code_size;
+        self.buffered_bits -= code_size;
+        Some(code)
+    }
+}
+
+/// Decompresses LZW-encoded image data into at most `max_indices` color indices, the number of
+/// pixels of the image, ignoring any excess.
+///
+/// Decoding stops at the End of Information code or when the data runs out, whichever comes
+/// first, since many encoders omit the former. Stopping at the size of the image keeps a small
+/// stream of codes that expand to long strings from taking up unbounded memory.
+pub fn decode(
+    lzw_minimum_code_size: u8,
+    data: SubBlocks<'_>,
+    max_indices: usize,
+) -> Result<Vec<u8>, DecodingError> {
+    decode_codes(lzw_minimum_code_size, data, max_indices, |_| {})
+}
+
+/// A code read from LZW-encoded image data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LZWCode {
+    pub code: u16,
+    // In bits, growing as the code table fills up
+    pub size: u8,
+}
+
+/// Decodes LZW-encoded image data like `decode`, along with every code read until decoding
+/// stopped, so that the codes can be shown next to the bytes holding them.
+pub fn trace_lzw(
+    lzw_minimum_code_size: u8,
+    data: SubBlocks<'_>,
+    max_indices: usize,
+) -> (Vec<LZWCode>, Result<Vec<u8>, DecodingError>) {
+    let mut codes = Vec::new();
+    let decoded = decode_codes(lzw_minimum_code_size, data, max_indices, |code| {
+        codes.push(code)
+    });
+    (codes, decoded)
+}
+
+fn decode_codes(
+    lzw_minimum_code_size: u8,
+    data: SubBlocks<'_>,
+    max_indices: usize,
+    mut on_code: impl FnMut(LZWCode),
+) -> Result<Vec<u8>, DecodingError> {
+    if lzw_minimum_code_size == 0 || lzw_minimum_code_size >= MAX_CODE_SIZE {
+        return Err(DecodingError::InvalidMinimumCodeSize(lzw_minimum_code_size));
+    }
+
+    let clear_code = 1u16 << lzw_minimum_code_size;
+    let end_code = clear_code + 1;
+
+    // every code is represented by the code of its prefix string and its last index
+    let mut prefixes = [0u16; MAX_CODES];
+    let mut suffixes = [0u8; MAX_CODES];
+    let mut first_indices = [0u8; MAX_CODES];
+    for code in 0..clear_code {
+        suffixes[code as usize] = code as u8;
+        first_indices[code as usize] = code as u8;
+    }
+
+    let mut reader = CodeReader {
+        bytes: data.into_iter(),
+        buffer: 0,
+        buffered_bits: 0,
+    };
+    let mut code_size = lzw_minimum_code_size + 1;
+    let mut next_code = end_code + 1;
+    let mut previous_code: Option<u16> = None;
+    let mut indices = Vec::new();
+    let mut string = Vec::new();
+
+    while let Some(code) = reader.read(code_size) {
+        on_code(LZWCode {
+            code,
+            size: code_size,
+        });
+        if code == clear_code {
+            code_size = lzw_minimum_code_size + 1;
+            next_code = end_code + 1;
+            previous_code = None;
+            continue;
+        }
+        if code == end_code {
+            break;
+        }
+
+        let previous = match previous_code {
+            None if code < clear_code => {
+                indices.push(code as u8);
+                previous_code = Some(code);
+                continue;
+            }
+            None => return Err(DecodingError::InvalidCode(code)),
+            Some(previous) => previous,
+        };
+
+        let first_index = if code < next_code {
+            first_indices[code as usize]
+        } else if code == next_code {
+            first_indices[previous as usize]
+        } else {
+            return Err(DecodingError::InvalidCode(code));
+        };
+
+        // deferred clear code: once the table is full it is used as is
+        if (next_code as usize) < MAX_CODES {
+            prefixes[next_code as usize] = previous;
+            suffixes[next_code as usize] = first_index;
+            first_indices[next_code as usize] = first_indices[previous as usize];
+            next_code += 1;
+            if next_code == 1 << code_size && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+
+        string.clear();
+        let mut current = code;
+        while current > end_code {
+            string.push(suffixes[current as usize]);
+            current = prefixes[current as usize];
+        }
+        string.push(current as u8);
+        indices.extend(string.iter().rev());
+        if indices.len() >= max_indices {
+            break;
+        }
+
+        previous_code = Some(code);
+    }
+
+    indices.truncate(max_indices);
+
+    Ok(indices)
+}
+
+/// Writes variable-length codes, least significant bit first, packed into sub-blocks.
+struct CodeWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    buffered_bits: u8,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, code_size: u8) {
+        self.buffer |= u32::from(code) << self.buffered_bits;
+        self.buffered_bits += code_size;
+        while self.buffered_bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.buffered_bits -= 8;
+        }
+    }
+
+    /// The number of bits written so far.
+    fn bits(&self) -> usize {
+        self.bytes.len() * 8 + self.buffered_bits as usize
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.buffered_bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        let mut data = Vec::with_capacity(self.bytes.len() + self.bytes.len() / 255 + 2);
+        for subblock in self.bytes.chunks(255) {
+            data.push(subblock.len() as u8);
+            data.extend_from_slice(subblock);
+        }
+        data.push(0);
+        data
+    }
+}
+
+/// The smallest LZW minimum code size that can represent all indices of a color table with the
+/// given number of colors. The GIF format does not allow code sizes smaller than 2.
+pub fn minimum_code_size(colors: usize) -> u8 {
+    let mut code_size = 2;
+    while (1 << code_size) < colors {
+        code_size += 1;
+    }
+    code_size
+}
+
+/// When the encoder starts over with an empty code table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClearStrategy {
+    // As soon as the code table is full, like most encoders
+    WhenFull,
+    // Never, compressing the rest of the image with the full code table (a "deferred clear")
+    Never,
+    // Once the code table is full, whenever the compression ratio drops, like Unix `compress`
+    OnRatioDrop,
+}
+
+// With `ClearStrategy::OnRatioDrop`, the number of indices between checks of the ratio
+const RATIO_CHECK_INTERVAL: usize = 1024;
+
+/// Compresses color indices, all of which must be smaller than `1 << lzw_minimum_code_size`,
+/// into data sub-blocks.
+///
+/// A clear code is emitted at the start and whenever the code table is full.
+pub fn encode(lzw_minimum_code_size: u8, indices: &[u8]) -> Vec<u8> {
+    encode_with_strategy(lzw_minimum_code_size, indices, ClearStrategy::WhenFull)
+}
+
+/// Like `encode`, but emitting clear codes according to the given strategy.
+pub fn encode_with_strategy(
+    lzw_minimum_code_size: u8,
+    indices: &[u8],
+    clear_strategy: ClearStrategy,
+) -> Vec<u8> {
+    compress(lzw_minimum_code_size, indices, clear_strategy, 0.0, None)
+}
+
+/// Like `encode`, but lossy in the manner of giflossy: when the current string cannot be extended
+/// by the next index, it may be extended by another index instead, as long as `distance` between
+/// the two is at most `tolerance`. The index closest to the original is picked.
+///
+/// Every decoded index is thus within `tolerance` of the original one.
+pub fn encode_lossy(
+    lzw_minimum_code_size: u8,
+    indices: &[u8],
+    tolerance: f32,
+    distance: impl Fn(u8, u8) -> f32,
+) -> Vec<u8> {
+    compress(
+        lzw_minimum_code_size,
+        indices,
+        ClearStrategy::WhenFull,
+        tolerance,
+        Some(&distance),
+    )
+}
+
+/// The longest string of the code table matching `indices`, along with its length. Every index
+/// but the first may be replaced by one of its substitutes, the nearest ones being tried first.
+fn longest_match(
+    children: &[u16],
+    alphabet_size: usize,
+    substitutes: &[Vec<u8>],
+    indices: &[u8],
+    stack: &mut Vec<(u16, usize)>,
+) -> (u16, usize) {
+    let mut longest = (u16::from(indices[0]), 1);
+    stack.push(longest);
+    while let Some((code, length)) = stack.pop() {
+        if length > longest.1 {
+            longest = (code, length);
+        }
+        if let Some(&next) = indices.get(length) {
+            for &substitute in substitutes[next as usize].iter().rev() {
+                let child = children[code as usize * alphabet_size + substitute as usize];
+                if child != 0 {
+                    stack.push((child, length + 1));
+                }
+            }
+        }
+    }
+    longest
+}
+
+/// The LZW compressor, which is lossy when given a distance between indices.
+fn compress(
+    lzw_minimum_code_size: u8,
+    indices: &[u8],
+    clear_strategy: ClearStrategy,
+    tolerance: f32,
+    distance: Option<&dyn Fn(u8, u8) -> f32>,
+) -> Vec<u8> {
+    let clear_code = 1u16 << lzw_minimum_code_size;
+    let end_code = clear_code + 1;
+    let alphabet_size = clear_code as usize;
+
+    // the code of every string extended by every index, where 0 means none
+    let mut children = vec![0u16; MAX_CODES * alphabet_size];
+    // each index itself, followed by the other indices within tolerance from nearest to farthest
+    let substitutes: Vec<Vec<u8>> = (0..alphabet_size.min(256))
+        .map(|index| {
+            let index = index as u8;
+            let mut substitutes = vec![(0.0, index)];
+            if let Some(distance) = distance {
+                substitutes.extend(
+                    (0..alphabet_size.min(256))
+                        .map(|other| other as u8)
+                        .filter(|&other| other != index)
+                        .map(|other| (distance(index, other), other))
+                        .filter(|&(distance, _)| distance <= tolerance),
+                );
+                substitutes.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            }
+            substitutes.into_iter().map(|(_, other)| other).collect()
+        })
+        .collect();
+    let mut stack = Vec::new();
+
+    let mut writer = CodeWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        buffered_bits: 0,
+    };
+    let mut code_size = lzw_minimum_code_size + 1;
+    let mut next_code = end_code + 1;
+    // with `ClearStrategy::OnRatioDrop`, where the code table was last cleared, in indices and
+    // bits, the last check of the ratio of indices per bit since then and the best ratio so far
+    let mut cleared_at = (0, 0);
+    let mut checked_at = 0;
+    let mut best_ratio = 0.0;
+
+    writer.write(clear_code, code_size);
+
+    let mut position = 0;
+    while position < indices.len() {
+        let (string, length) = longest_match(
+            &children,
+            alphabet_size,
+            &substitutes,
+            &indices[position..],
+            &mut stack,
+        );
+        writer.write(string, code_size);
+        position += length;
+        let index = match indices.get(position) {
+            Some(&index) => index,
+            None => break,
+        };
+
+        // once the table is full, the decoder keeps using it as is until the next clear code
+        if (next_code as usize) < MAX_CODES {
+            children[string as usize * alphabet_size + index as usize] = next_code;
+            next_code += 1;
+            // the decoder adds codes one step behind the encoder
+            if next_code > 1 << code_size && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+
+        if next_code as usize != MAX_CODES {
+            continue;
+        }
+        let clear = match clear_strategy {
+            ClearStrategy::WhenFull => true,
+            ClearStrategy::Never => false,
+            ClearStrategy::OnRatioDrop => {
+                if position < checked_at + RATIO_CHECK_INTERVAL {
+                    continue;
+                }
+                checked_at = position;
+                let ratio =
+                    (position - cleared_at.0) as f64 / (writer.bits() - cleared_at.1) as f64;
+                if ratio > best_ratio {
+                    best_ratio = ratio;
+                    false
+                } else {
+                    cleared_at = (position, writer.bits());
+                    best_ratio = 0.0;
+                    true
+                }
+            }
+        };
+        if clear {
+            writer.write(clear_code, code_size);
+            for child in children.iter_mut() {
+                *child = 0;
+            }
+            code_size = lzw_minimum_code_size + 1;
+            next_code = end_code + 1;
+        }
+    }
+
+    // reading the last code makes the decoder add one more code
+    if next_code > end_code + 1 && next_code + 1 > 1 << code_size && code_size < MAX_CODE_SIZE {
+        code_size += 1;
+    }
+    writer.write(end_code, code_size);
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_decode_sample_image() {
+        // the image data of fixtures/sample_1.gif
+        let data = [
+            0x16, 0x8c, 0x2d, 0x99, 0x87, 0x2a, 0x1c, 0xdc, 0x33, 0xa0, 0x02, 0x75, 0xec, 0x95,
+            0xfa, 0xa8, 0xde, 0x60, 0x8c, 0x04, 0x91, 0x4c, 0x01, 0x00,
+        ];
+        assert_eq!(
+            decode(2, SubBlocks(&data[..]), 100),
+            Ok(vec![
+                1, 1, 1, 1, 1, 2, 2, 2, 2, 2, //
+                1, 1, 1, 1, 1, 2, 2, 2, 2, 2, //
+                1, 1, 1, 1, 1, 2, 2, 2, 2, 2, //
+                1, 1, 1, 0, 0, 0, 0, 2, 2, 2, //
+                1, 1, 1, 0, 0, 0, 0, 2, 2, 2, //
+                2, 2, 2, 0, 0, 0, 0, 1, 1, 1, //
+                2, 2, 2, 0, 0, 0, 0, 1, 1, 1, //
+                2, 2, 2, 2, 2, 1, 1, 1, 1, 1, //
+                2, 2, 2, 2, 2, 1, 1, 1, 1, 1, //
+                2, 2, 2, 2, 2, 1, 1, 1, 1, 1, //
+            ])
+        );
+    }
+
+    #[test]
+    fn should_stop_when_data_runs_out() {
+        // clear code, 1, 1, 6, 1 without an End of Information code
+        assert_eq!(
+            decode(2, SubBlocks(&[0x02, 0x4c, 0x1c, 0x00][..]), 16),
+            Ok(vec![1, 1, 1, 1, 1])
+        );
+    }
+
+    #[test]
+    fn should_stop_at_image_size() {
+        // a million indices compress to a few kilobytes
+        let indices = vec![1; 1_000_000];
+        let encoded = encode(2, &indices);
+        assert!(encoded.len() < 10_000);
+        assert_eq!(decode(2, SubBlocks(&encoded), 100), Ok(vec![1; 100]));
+        assert_eq!(decode(2, SubBlocks(&encoded), 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn should_trace_codes() {
+        // clear code, 1, 1, 6, 1 without an End of Information code
+        let (codes, decoded) = trace_lzw(2, SubBlocks(&[0x02, 0x4c, 0x1c, 0x00][..]), 16);
+        let codes: Vec<_> = codes.iter().map(|code| (code.code, code.size)).collect();
+        assert_eq!(codes, vec![(4, 3), (1, 3), (1, 3), (6, 3), (1, 4)]);
+        assert_eq!(decoded, Ok(vec![1, 1, 1, 1, 1]));
+
+        let (codes, decoded) = trace_lzw(2, SubBlocks(&[0x01, 0x3c, 0x00][..]), 16);
+        assert_eq!(codes.len(), 2);
+        assert_eq!(decoded, Err(DecodingError::InvalidCode(7)));
+    }
+
+    #[test]
+    fn should_fail_on_invalid_code() {
+        // clear code followed by code 7, which is not in the table yet
+        assert_eq!(
+            decode(2, SubBlocks(&[0x01, 0x3c, 0x00][..]), 16),
+            Err(DecodingError::InvalidCode(7))
+        );
+        assert_eq!(
+            decode(0, SubBlocks(&[0x00][..]), 16),
+            Err(DecodingError::InvalidMinimumCodeSize(0))
+        );
+    }
+
+    #[test]
+    fn should_encode_sample_image() {
+        let indices = decode(
+            2,
+            SubBlocks(
+                &[
+                    0x16, 0x8c, 0x2d, 0x99, 0x87, 0x2a, 0x1c, 0xdc, 0x33, 0xa0, 0x02, 0x75, 0xec,
+                    0x95, 0xfa, 0xa8, 0xde, 0x60, 0x8c, 0x04, 0x91, 0x4c, 0x01, 0x00,
+                ][..],
+            ),
+            100,
+        )
+        .unwrap();
+        let encoded = encode(2, &indices);
+        assert_eq!(decode(2, SubBlocks(&encoded), indices.len()), Ok(indices));
+    }
+
+    #[test]
+    fn should_encode_empty_image() {
+        let encoded = encode(2, &[]);
+        assert_eq!(decode(2, SubBlocks(&encoded), 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn should_round_trip_through_full_code_tables() {
+        // a simple pseudo-random sequence fills the code table several times
+        let mut state = 1u32;
+        for &lzw_minimum_code_size in &[2u8, 3, 5, 8] {
+            let indices: Vec<u8> = (0..200_000)
+                .map(|_| {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    ((state >> 16) % (1 << lzw_minimum_code_size)) as u8
+                })
+                .collect();
+            let encoded = encode(lzw_minimum_code_size, &indices);
+            assert_eq!(
+                decode(lzw_minimum_code_size, SubBlocks(&encoded), indices.len()),
+                Ok(indices)
+            );
+        }
+
+        let indices = vec![7; 100_000];
+        let encoded = encode(3, &indices);
+        assert_eq!(decode(3, SubBlocks(&encoded), indices.len()), Ok(indices));
+    }
+
+    #[test]
+    fn should_round_trip_with_every_clear_strategy() {
+        // noise with long runs, whose statistics change halfway through
+        let mut state = 1u32;
+        let indices: Vec<u8> = (0..100_000)
+            .map(|position| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let colors = if position < 50_000 { 4 } else { 256 };
+                ((state >> 16) % colors) as u8
+            })
+            .flat_map(|index| vec![index; usize::from(index % 3) + 1])
+            .collect();
+        for &clear_strategy in &[
+            ClearStrategy::WhenFull,
+            ClearStrategy::Never,
+            ClearStrategy::OnRatioDrop,
+        ] {
+            let encoded = encode_with_strategy(8, &indices, clear_strategy);
+            assert_eq!(
+                decode(8, SubBlocks(&encoded), indices.len()),
+                Ok(indices.clone()),
+                "{:?}",
+                clear_strategy
+            );
+        }
+        assert_eq!(
+            encode_with_strategy(8, &indices, ClearStrategy::WhenFull),
+            encode(8, &indices)
+        );
+        assert_ne!(
+            encode_with_strategy(8, &indices, ClearStrategy::OnRatioDrop),
+            encode_with_strategy(8, &indices, ClearStrategy::Never)
+        );
+    }
+
+    #[test]
+    fn should_compress_better_within_tolerance() {
+        // a noisy ramp, where neighbouring indices stand for similar colors
+        let mut state = 1u32;
+        let indices: Vec<u8> = (0..20_000u32)
+            .map(|position| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((position / 100 % 200) + (state >> 16) % 8) as u8
+            })
+            .collect();
+        let distance = |a: u8, b: u8| (f32::from(a) - f32::from(b)).abs();
+
+        let lossless = encode(8, &indices);
+        assert_eq!(encode_lossy(8, &indices, 0.0, distance), lossless);
+
+        let lossy = encode_lossy(8, &indices, 4.0, distance);
+        assert!(
+            lossy.len() * 2 < lossless.len(),
+            "{} {}",
+            lossy.len(),
+            lossless.len()
+        );
+        let decoded = decode(8, SubBlocks(&lossy), indices.len()).unwrap();
+        assert_eq!(decoded.len(), indices.len());
+        assert!(decoded
+            .iter()
+            .zip(&indices)
+            .all(|(&a, &b)| distance(a, b) <= 4.0));
+    }
+
+    #[test]
+    fn should_round_trip_short_images() {
+        for len in 0..300 {
+            let indices: Vec<u8> = (0..len).map(|i| (i * 7 % 11 % 4) as u8).collect();
+            let encoded = encode(2, &indices);
+            assert_eq!(decode(2, SubBlocks(&encoded), indices.len()), Ok(indices));
+        }
+    }
+
+    #[test]
+    fn should_compute_minimum_code_size() {
+        assert_eq!(minimum_code_size(1), 2);
+        assert_eq!(minimum_code_size(4), 2);
+        assert_eq!(minimum_code_size(5), 3);
+        assert_eq!(minimum_code_size(256), 8);
+    }
+}
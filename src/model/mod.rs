@@ -1,7 +1,17 @@
+#[cfg(feature = "serde")]
+mod serialize;
+
+#[cfg(feature = "serde")]
+pub use self::serialize::{DeserializedBlock, DeserializedGIF};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Part of the Header. Supported versions are "87a" and "89a".
 ///
 /// See the GIF89a spec §17
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum GIFVersion {
     GIF89a,
     GIF87a,
@@ -9,6 +19,7 @@ pub enum GIFVersion {
 
 /// See the GIF89a spec §23
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GraphicControlExtension {
     pub byte_size: u8,
     pub packed_field: u8,
@@ -16,10 +27,76 @@ pub struct GraphicControlExtension {
     pub transparent_color_index: u8,
 }
 
+/// The way in which a graphic is to be treated after being displayed.
+///
+/// See the GIF89a spec §23
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DisposalMethod {
+    // No disposal specified, the decoder is not required to take any action
+    Unspecified,
+    // The graphic is to be left in place
+    DoNotDispose,
+    // The area used by the graphic must be restored to the background color
+    RestoreToBackgroundColor,
+    // The area overwritten by the graphic must be restored to what was there prior to rendering
+    RestoreToPrevious,
+    // Values 4-7 are reserved
+    Reserved(u8),
+}
+
+impl DisposalMethod {
+    pub(crate) fn value(self) -> u8 {
+        match self {
+            DisposalMethod::Unspecified => 0,
+            DisposalMethod::DoNotDispose => 1,
+            DisposalMethod::RestoreToBackgroundColor => 2,
+            DisposalMethod::RestoreToPrevious => 3,
+            DisposalMethod::Reserved(value) => value & 0b_111,
+        }
+    }
+}
+
+impl GraphicControlExtension {
+    pub fn new(
+        disposal_method: DisposalMethod,
+        delay_time: u16,
+        transparent_color_index: Option<u8>,
+    ) -> GraphicControlExtension {
+        GraphicControlExtension {
+            byte_size: 4,
+            packed_field: disposal_method.value() << 2
+                | if transparent_color_index.is_some() {
+                    0b_0000_0001
+                } else {
+                    0
+                },
+            delay_time,
+            transparent_color_index: transparent_color_index.unwrap_or(0),
+        }
+    }
+
+    pub fn disposal_method(&self) -> DisposalMethod {
+        match (self.packed_field & 0b_0001_1100) >> 2 {
+            0 => DisposalMethod::Unspecified,
+            1 => DisposalMethod::DoNotDispose,
+            2 => DisposalMethod::RestoreToBackgroundColor,
+            3 => DisposalMethod::RestoreToPrevious,
+            value => DisposalMethod::Reserved(value),
+        }
+    }
+
+    /// Whether `transparent_color_index` is in use.
+    pub fn transparent_color_flag(&self) -> bool {
+        self.packed_field & 0b_0000_0001 != 0
+    }
+}
+
 /// A required block of the Table-Based Image containing the description of an image.
 ///
 /// See the GIF89a spec §20
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ImageDescriptor {
     pub left: u16,
     pub top: u16,
@@ -28,17 +105,33 @@ pub struct ImageDescriptor {
     pub packed_field: u8,
 }
 
+impl ImageDescriptor {
+    /// Whether a Local Color Table immediately follows this Image Descriptor.
+    pub fn local_color_table_flag(&self) -> bool {
+        self.packed_field & 0b_1000_0000 != 0
+    }
+
+    /// Whether the image is stored in the four-pass interlaced order of Appendix E.
+    pub fn interlace_flag(&self) -> bool {
+        self.packed_field & 0b_0100_0000 != 0
+    }
+}
+
 /// A collections of sub-blocks, each one preceded by a u8 byte denoting its size and terminated by
 /// a zero-sized sub-block (block terminator).
 ///
 /// See the GIF89a spec §15-16
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct SubBlocks<'a>(pub &'a [u8]);
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct SubBlocks<'a>(
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize::bytes"))] pub &'a [u8],
+);
 
-/// Table Based Image Data. 
+/// Table Based Image Data.
 ///
 /// See the GIF89a spec §22
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct ImageData<'a> {
     pub lzw_minimum_code_size: u8,
     pub data: SubBlocks<'a>,
@@ -47,13 +140,17 @@ pub struct ImageData<'a> {
 /// The various data blocks that comprise the content of a GIF.
 ///
 /// See the GIF89a spec §12 and Appendix B.
+///
+/// With the `serde` feature, a serialized block deserializes as a `DeserializedBlock`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum Block<'a> {
     // Graphic Block with Graphic-Rendering Block
     GraphicBlock {
         graphic_control_extension: Option<GraphicControlExtension>,
         // Table-Based Image:
         image_descriptor: ImageDescriptor,
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize::optional_bytes"))]
         local_color_table: Option<&'a [u8]>,
         image_data: ImageData<'a>,
     },
@@ -64,16 +161,79 @@ pub enum Block<'a> {
     },
     ApplicationExtension(SubBlocks<'a>),
     CommentExtension(SubBlocks<'a>),
+    // A Graphic Control Extension that is not followed by a graphic rendering block, preserved as
+    // is
+    GraphicControlExtension(GraphicControlExtension),
+    // Any other extension (e.g. a proprietary one), preserved as is
+    UnknownExtension {
+        label: u8,
+        data: SubBlocks<'a>,
+    },
 }
 
 /// The full structure of a GIF.
 ///
 /// See Appendix B.
-#[derive(Debug, PartialEq, Eq)]
+///
+/// With the `serde` feature, a serialized GIF deserializes as a `DeserializedGIF`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct GIF<'a> {
     pub version: GIFVersion,
     pub width: u16,
     pub height: u16,
+    pub packed_field: u8,
+    pub background_color_index: u8,
+    pub pixel_aspect_ratio: u8,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize::optional_bytes"))]
     pub global_color_table: Option<&'a [u8]>,
     pub data: Vec<Block<'a>>,
+    // Whether the data stream was properly terminated by a Trailer (0x3B)
+    pub trailer: bool,
+}
+
+impl<'a> Block<'a> {
+    /// The loop count of a NETSCAPE2.0 or ANIMEXTS1.0 application extension.
+    pub(crate) fn loop_count(&self) -> Option<u16> {
+        match self {
+            Block::ApplicationExtension(data) => {
+                let mut subblocks = data.slices();
+                match (subblocks.next(), subblocks.next()) {
+                    (Some(b"NETSCAPE2.0"), Some(&[0x01, low, high]))
+                    | (Some(b"ANIMEXTS1.0"), Some(&[0x01, low, high])) => {
+                        Some(u16::from_le_bytes([low, high]))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> GIF<'a> {
+    /// The number of times an animation should be repeated according to the NETSCAPE2.0 (or
+    /// ANIMEXTS1.0) application extension, where 0 means forever. `None` if the extension is
+    /// missing, in which case the animation should be played once.
+    pub fn loop_count(&self) -> Option<u16> {
+        self.data.iter().find_map(Block::loop_count)
+    }
+
+    /// The oldest version of the spec that supports all the blocks in this GIF: "87a" if there are
+    /// no extension blocks and "89a" otherwise.
+    pub fn required_version(&self) -> GIFVersion {
+        let has_extensions = self.data.iter().any(|block| match block {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                ..
+            } => graphic_control_extension.is_some(),
+            _ => true,
+        });
+
+        if has_extensions {
+            GIFVersion::GIF89a
+        } else {
+            GIFVersion::GIF87a
+        }
+    }
 }
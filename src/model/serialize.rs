@@ -0,0 +1,185 @@
+//! Byte data is serialized as base64 in human-readable formats such as JSON, and as is otherwise.
+//!
+//! Types that borrow from the parsed data only implement `Serialize`, as their byte data cannot be
+//! borrowed back from base64. They are deserialized into owned counterparts instead, which lend
+//! them out.
+
+use super::{
+    Block, GIFVersion, GraphicControlExtension, ImageData, ImageDescriptor, SubBlocks, GIF,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serializer};
+use std::fmt;
+
+pub fn bytes<S: Serializer>(bytes: &&[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    } else {
+        serializer.serialize_bytes(bytes)
+    }
+}
+
+pub fn optional_bytes<S: Serializer>(
+    bytes: &Option<&[u8]>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match bytes {
+        Some(bytes) => self::bytes(bytes, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "base64 or bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Vec<u8>, E> {
+        STANDARD.decode(value).map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(value.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(value)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+fn owned_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_str(BytesVisitor)
+    } else {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+struct OwnedBytes(#[serde(deserialize_with = "owned_bytes")] Vec<u8>);
+
+fn optional_owned_bytes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<u8>>, D::Error> {
+    Option::<OwnedBytes>::deserialize(deserializer)
+        .map(|bytes| bytes.map(|OwnedBytes(bytes)| bytes))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+struct OwnedImageData {
+    lzw_minimum_code_size: u8,
+    data: OwnedBytes,
+}
+
+/// The owned counterpart of `Block`, with the same variants and fields.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+enum OwnedBlock {
+    GraphicBlock {
+        graphic_control_extension: Option<GraphicControlExtension>,
+        image_descriptor: ImageDescriptor,
+        #[serde(deserialize_with = "optional_owned_bytes")]
+        local_color_table: Option<Vec<u8>>,
+        image_data: OwnedImageData,
+    },
+    TextBlock {
+        graphic_control_extension: Option<GraphicControlExtension>,
+        text: OwnedBytes,
+    },
+    ApplicationExtension(OwnedBytes),
+    CommentExtension(OwnedBytes),
+    GraphicControlExtension(GraphicControlExtension),
+    UnknownExtension {
+        label: u8,
+        data: OwnedBytes,
+    },
+}
+
+/// A `Block` deserialized from the form it is serialized to. It owns the byte data and lends it
+/// out as a `Block`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct DeserializedBlock(OwnedBlock);
+
+impl DeserializedBlock {
+    pub fn block(&self) -> Block<'_> {
+        match &self.0 {
+            OwnedBlock::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } => Block::GraphicBlock {
+                graphic_control_extension: *graphic_control_extension,
+                image_descriptor: *image_descriptor,
+                local_color_table: local_color_table.as_deref(),
+                image_data: ImageData {
+                    lzw_minimum_code_size: image_data.lzw_minimum_code_size,
+                    data: SubBlocks(&image_data.data.0),
+                },
+            },
+            OwnedBlock::TextBlock {
+                graphic_control_extension,
+                text,
+            } => Block::TextBlock {
+                graphic_control_extension: *graphic_control_extension,
+                text: SubBlocks(&text.0),
+            },
+            OwnedBlock::ApplicationExtension(data) => {
+                Block::ApplicationExtension(SubBlocks(&data.0))
+            }
+            OwnedBlock::CommentExtension(data) => Block::CommentExtension(SubBlocks(&data.0)),
+            OwnedBlock::GraphicControlExtension(graphic_control_extension) => {
+                Block::GraphicControlExtension(*graphic_control_extension)
+            }
+            OwnedBlock::UnknownExtension { label, data } => Block::UnknownExtension {
+                label: *label,
+                data: SubBlocks(&data.0),
+            },
+        }
+    }
+}
+
+/// A `GIF` deserialized from the form it is serialized to. Like `Encoder`, it owns the byte data
+/// and lends it out as a `GIF`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct DeserializedGIF {
+    version: GIFVersion,
+    width: u16,
+    height: u16,
+    packed_field: u8,
+    background_color_index: u8,
+    pixel_aspect_ratio: u8,
+    #[serde(deserialize_with = "optional_owned_bytes")]
+    global_color_table: Option<Vec<u8>>,
+    data: Vec<DeserializedBlock>,
+    trailer: bool,
+}
+
+impl DeserializedGIF {
+    pub fn gif(&self) -> GIF<'_> {
+        GIF {
+            version: self.version,
+            width: self.width,
+            height: self.height,
+            packed_field: self.packed_field,
+            background_color_index: self.background_color_index,
+            pixel_aspect_ratio: self.pixel_aspect_ratio,
+            global_color_table: self.global_color_table.as_deref(),
+            data: self.data.iter().map(DeserializedBlock::block).collect(),
+            trailer: self.trailer,
+        }
+    }
+}
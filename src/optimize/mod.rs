@@ -0,0 +1,535 @@
+mod reencode;
+
+use super::encoder::{quantize_animation, IndexedFrame};
+use super::lzw::{encode, minimum_code_size};
+use super::model::{DisposalMethod, ImageDescriptor};
+use super::quantize::Quantizer;
+use super::render::{indices_to_rgba, RgbaImage};
+
+pub use self::reencode::{GIFOptimizer, OptimizedGIF};
+
+/// A frame reduced to the rectangle of the canvas that changes when it is drawn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CroppedFrame {
+    pub left: u16,
+    pub top: u16,
+    pub image: RgbaImage,
+    // How the frame is disposed of before the next one is drawn
+    pub disposal_method: DisposalMethod,
+}
+
+/// A rectangle of the canvas.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Rectangle {
+    left: u16,
+    top: u16,
+    width: u16,
+    height: u16,
+}
+
+impl Rectangle {
+    fn area(self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// The smallest rectangle containing both.
+    fn union(self, other: Rectangle) -> Rectangle {
+        let left = self.left.min(other.left);
+        let top = self.top.min(other.top);
+        let right = (self.left + self.width).max(other.left + other.width);
+        let bottom = (self.top + self.height).max(other.top + other.height);
+        Rectangle {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    fn image_descriptor(self) -> ImageDescriptor {
+        ImageDescriptor {
+            left: self.left,
+            top: self.top,
+            width: self.width,
+            height: self.height,
+            packed_field: 0,
+        }
+    }
+}
+
+fn is_transparent(pixel: &[u8]) -> bool {
+    pixel[3] == 0
+}
+
+/// The bounding rectangle of the pixels matching a predicate, given the pixels of two images.
+fn bounding_rectangle(
+    width: u16,
+    before: &RgbaImage,
+    after: &RgbaImage,
+    predicate: impl Fn(&[u8], &[u8]) -> bool,
+) -> Option<Rectangle> {
+    let width = width as usize;
+    let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+    for (index, (before, after)) in before
+        .pixels
+        .chunks_exact(4)
+        .zip(after.pixels.chunks_exact(4))
+        .enumerate()
+    {
+        if predicate(before, after) {
+            let (x, y) = (index % width, index / width);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+
+    if right == 0 {
+        None
+    } else {
+        Some(Rectangle {
+            left: left as u16,
+            top: top as u16,
+            width: (right - left) as u16,
+            height: (bottom - top) as u16,
+        })
+    }
+}
+
+/// The pixels that are opaque in `before` and transparent in `after`, which only clearing can
+/// achieve.
+fn vanishing_rectangle(before: &RgbaImage, after: &RgbaImage) -> Option<Rectangle> {
+    bounding_rectangle(before.width, before, after, |before, after| {
+        !is_transparent(before) && is_transparent(after)
+    })
+}
+
+/// The smallest rectangle to draw on `canvas` to turn it into `target`, or `None` when that is
+/// impossible because some opaque pixel of `canvas` has to become transparent.
+fn changed_rectangle(canvas: &RgbaImage, target: &RgbaImage) -> Option<Rectangle> {
+    if vanishing_rectangle(canvas, target).is_some() {
+        return None;
+    }
+    let changed = bounding_rectangle(canvas.width, canvas, target, |before, after| {
+        before != after && !(is_transparent(before) && is_transparent(after))
+    });
+    // an identical frame still needs an image, so a single unchanged pixel is drawn
+    Some(changed.unwrap_or(Rectangle {
+        left: 0,
+        top: 0,
+        width: 1,
+        height: 1,
+    }))
+}
+
+/// Crops composited frames of an animation, all the size of the Logical Screen, down to the
+/// rectangles that change from one frame to the next.
+///
+/// The disposal method of each frame is picked to minimize the next rectangle, like gifsicle's
+/// `-O2`. Restoring to the background color is the only way to make opaque pixels transparent
+/// again, which it does to transparent rather than to the background color, as in `Renderer`.
+/// The first frame keeps the whole Logical Screen, since viewers differ in how they initialize
+/// the canvas.
+pub fn crop_frames(frames: &[RgbaImage]) -> Vec<CroppedFrame> {
+    let mut cropped_frames: Vec<CroppedFrame> = Vec::with_capacity(frames.len());
+    // The canvas on which the previous frame was drawn
+    let mut previous_canvas = match frames.first() {
+        Some(first) => RgbaImage::new(first.width, first.height),
+        None => return cropped_frames,
+    };
+    let mut previous_rectangle = Rectangle {
+        left: 0,
+        top: 0,
+        width: previous_canvas.width,
+        height: previous_canvas.height,
+    };
+
+    for (index, frame) in frames.iter().enumerate() {
+        if index > 0 {
+            let previous_frame = &frames[index - 1];
+            // Pixels that must become transparent outside the previous rectangle can be cleared
+            // too, by enlarging it
+            let cleared_rectangle = match vanishing_rectangle(previous_frame, frame) {
+                Some(vanishing) => previous_rectangle.union(vanishing),
+                None => previous_rectangle,
+            };
+            let mut cleared = previous_frame.clone();
+            cleared.clear(&cleared_rectangle.image_descriptor());
+
+            // The canvas left by each disposal method of the previous frame along with the area
+            // it adds to that frame, in order of preference when they cost the same
+            let candidates = vec![
+                (DisposalMethod::DoNotDispose, previous_frame.clone(), 0),
+                (
+                    DisposalMethod::RestoreToBackgroundColor,
+                    cleared,
+                    cleared_rectangle.area() - previous_rectangle.area(),
+                ),
+                (DisposalMethod::RestoreToPrevious, previous_canvas, 0),
+            ];
+            let (disposal_method, canvas, rectangle) = candidates
+                .into_iter()
+                .filter_map(|(disposal_method, canvas, added_area)| {
+                    changed_rectangle(&canvas, frame)
+                        .map(|rectangle| (disposal_method, canvas, rectangle, added_area))
+                })
+                .min_by_key(|(_, _, rectangle, added_area)| rectangle.area() + added_area)
+                .map(|(disposal_method, canvas, rectangle, _)| (disposal_method, canvas, rectangle))
+                .expect("clearing every vanishing pixel allows any next frame");
+
+            if let Some(previous) = cropped_frames.last_mut() {
+                previous.disposal_method = disposal_method;
+                if disposal_method == DisposalMethod::RestoreToBackgroundColor {
+                    previous.left = cleared_rectangle.left;
+                    previous.top = cleared_rectangle.top;
+                    previous.image = previous_frame.crop(
+                        cleared_rectangle.left,
+                        cleared_rectangle.top,
+                        cleared_rectangle.width,
+                        cleared_rectangle.height,
+                    );
+                }
+            }
+            previous_canvas = canvas;
+            previous_rectangle = rectangle;
+        }
+
+        cropped_frames.push(CroppedFrame {
+            left: previous_rectangle.left,
+            top: previous_rectangle.top,
+            image: frame.crop(
+                previous_rectangle.left,
+                previous_rectangle.top,
+                previous_rectangle.width,
+                previous_rectangle.height,
+            ),
+            disposal_method: DisposalMethod::DoNotDispose,
+        });
+    }
+    cropped_frames
+}
+
+// Size of a Graphic Control Extension
+const GRAPHIC_CONTROL_EXTENSION_SIZE: usize = 8;
+
+/// The size of a frame's compressed image data, Local Color Table and Graphic Control Extension.
+fn encoded_size(frame: &IndexedFrame, global_color_table: Option<&[u8]>) -> usize {
+    let colors = frame
+        .local_color_table
+        .as_deref()
+        .or(global_color_table)
+        .map_or(0, |color_table| color_table.len() / 3);
+    let local_color_table_size = frame.local_color_table.as_ref().map_or(0, |color_table| {
+        3 * (color_table.len() / 3).next_power_of_two().max(2)
+    });
+    let graphic_control_extension_size = if frame.needs_graphic_control_extension() {
+        GRAPHIC_CONTROL_EXTENSION_SIZE
+    } else {
+        0
+    };
+    encode(minimum_code_size(colors), &frame.indices).len()
+        + local_color_table_size
+        + graphic_control_extension_size
+}
+
+/// Replaces the pixels of a frame that already show on the canvas beneath it by a transparent
+/// color, so that LZW compresses them into long runs. The frame keeps its transparent color if it
+/// has one, and otherwise takes a color it does not use, or a new color in a Local Color Table.
+///
+/// The change is only kept when it makes the frame smaller, and returns whether it was. Either way
+/// the rendered animation stays the same.
+pub fn make_unchanged_pixels_transparent(
+    frame: &mut IndexedFrame,
+    canvas: &RgbaImage,
+    global_color_table: Option<&[u8]>,
+) -> bool {
+    let color_table = match frame.local_color_table.as_deref().or(global_color_table) {
+        Some(color_table) => color_table,
+        None => return false,
+    };
+    let colors = color_table.len() / 3;
+
+    let mut optimized = frame.clone();
+    let transparent_color_index = match frame.transparent_color_index {
+        Some(transparent_color_index) => transparent_color_index,
+        None => {
+            let mut used = [false; 256];
+            for &index in &frame.indices {
+                used[index as usize] = true;
+            }
+            match (0..colors.min(256)).find(|&index| !used[index]) {
+                Some(index) => index as u8,
+                None if colors < 256 => {
+                    let mut local_color_table = color_table.to_vec();
+                    local_color_table.extend_from_slice(&[0, 0, 0]);
+                    optimized.local_color_table = Some(local_color_table);
+                    colors as u8
+                }
+                None => return false,
+            }
+        }
+    };
+    optimized.transparent_color_index = Some(transparent_color_index);
+
+    let width = frame.width as usize;
+    for (position, index) in optimized.indices.iter_mut().enumerate() {
+        let x = frame.left as usize + position % width;
+        let y = frame.top as usize + position / width;
+        let i = *index as usize * 3;
+        if x >= canvas.width as usize
+            || y >= canvas.height as usize
+            || *index == transparent_color_index
+            || i + 3 > color_table.len()
+        {
+            continue;
+        }
+        let color = [color_table[i], color_table[i + 1], color_table[i + 2], 0xff];
+        if canvas.pixel(x as u16, y as u16) == color {
+            *index = transparent_color_index;
+        }
+    }
+
+    if encoded_size(&optimized, global_color_table) < encoded_size(frame, global_color_table) {
+        *frame = optimized;
+        true
+    } else {
+        false
+    }
+}
+
+/// Encodes composited frames of an animation compactly: cropped with `crop_frames`, quantized
+/// with `quantize_animation` and with unchanged pixels made transparent where that helps.
+///
+/// Returns the Global Color Table and the frames, whose delays are left for the caller to set.
+pub fn optimize_animation(
+    frames: &[RgbaImage],
+    quantizer: &Quantizer,
+    max_error: f32,
+) -> (Vec<u8>, Vec<IndexedFrame>) {
+    let cropped_frames = crop_frames(frames);
+    let images: Vec<_> = cropped_frames
+        .iter()
+        .map(|cropped_frame| cropped_frame.image.clone())
+        .collect();
+    let (global_color_table, mut indexed_frames) =
+        quantize_animation(&images, quantizer, max_error);
+
+    // The canvas as it is displayed, which may differ from `frames` after quantization
+    let mut canvas = frames.first().map_or(RgbaImage::new(0, 0), |first| {
+        RgbaImage::new(first.width, first.height)
+    });
+    for (frame, cropped_frame) in indexed_frames.iter_mut().zip(&cropped_frames) {
+        frame.left = cropped_frame.left;
+        frame.top = cropped_frame.top;
+        frame.disposal_method = cropped_frame.disposal_method;
+        make_unchanged_pixels_transparent(frame, &canvas, Some(&global_color_table));
+
+        let color_table = frame
+            .local_color_table
+            .as_deref()
+            .unwrap_or(&global_color_table);
+        let image = RgbaImage {
+            width: frame.width,
+            height: frame.height,
+            pixels: indices_to_rgba(&frame.indices, color_table, frame.transparent_color_index),
+        };
+        let previous_canvas = canvas.clone();
+        canvas.draw(&image, frame.left, frame.top);
+        match frame.disposal_method {
+            DisposalMethod::RestoreToBackgroundColor => canvas.clear(&ImageDescriptor {
+                left: frame.left,
+                top: frame.top,
+                width: frame.width,
+                height: frame.height,
+                packed_field: 0,
+            }),
+            DisposalMethod::RestoreToPrevious => canvas = previous_canvas,
+            _ => {}
+        }
+    }
+
+    (global_color_table, indexed_frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::encoder::Encoder;
+    use super::super::quantize::QuantizationMethod;
+    use super::super::render::Renderer;
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn image(rows: &[&[[u8; 4]]]) -> RgbaImage {
+        RgbaImage {
+            width: rows[0].len() as u16,
+            height: rows.len() as u16,
+            pixels: rows
+                .iter()
+                .flat_map(|row| row.iter().flat_map(|pixel| pixel.iter().cloned()))
+                .collect(),
+        }
+    }
+
+    /// Encodes cropped frames and renders them back.
+    fn render(cropped_frames: &[CroppedFrame], width: u16, height: u16) -> Vec<RgbaImage> {
+        let images: Vec<_> = cropped_frames
+            .iter()
+            .map(|frame| frame.image.clone())
+            .collect();
+        let quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+        let (global_color_table, frames) = quantize_animation(&images, &quantizer, 0.0);
+        let mut encoder = Encoder::new(width, height, Some(&global_color_table)).unwrap();
+        for (mut frame, cropped_frame) in frames.into_iter().zip(cropped_frames) {
+            frame.left = cropped_frame.left;
+            frame.top = cropped_frame.top;
+            frame.disposal_method = cropped_frame.disposal_method;
+            encoder.add_frame(&frame).unwrap();
+        }
+        let gif = encoder.gif();
+        Renderer::new(&gif)
+            .map(|frame| frame.unwrap().image)
+            .collect()
+    }
+
+    #[test]
+    fn should_crop_to_changed_rectangle() {
+        let frames = [
+            image(&[&[RED, RED, RED], &[RED, RED, RED], &[RED, RED, RED]]),
+            image(&[&[RED, RED, RED], &[RED, BLUE, RED], &[RED, RED, BLUE]]),
+            image(&[&[RED, RED, RED], &[RED, BLUE, RED], &[RED, RED, BLUE]]),
+        ];
+        let cropped_frames = crop_frames(&frames);
+
+        assert_eq!(cropped_frames[0].image, frames[0]);
+        assert_eq!(
+            cropped_frames[0].disposal_method,
+            DisposalMethod::DoNotDispose
+        );
+        assert_eq!((cropped_frames[1].left, cropped_frames[1].top), (1, 1));
+        assert_eq!(
+            cropped_frames[1].image,
+            image(&[&[BLUE, RED], &[RED, BLUE]])
+        );
+        assert_eq!(cropped_frames[2].image, image(&[&[RED]]));
+        assert_eq!(render(&cropped_frames, 3, 3), frames);
+    }
+
+    #[test]
+    fn should_pick_disposal_methods() {
+        let frames = [
+            image(&[&[RED, RED, RED, RED]]),
+            image(&[&[RED, BLUE, BLUE, RED]]),
+            image(&[&[RED, RED, RED, RED]]),
+            image(&[&[CLEAR, RED, RED, RED]]),
+        ];
+        let cropped_frames = crop_frames(&frames);
+
+        let disposal_methods: Vec<_> = cropped_frames
+            .iter()
+            .map(|frame| frame.disposal_method)
+            .collect();
+        assert_eq!(
+            disposal_methods,
+            vec![
+                DisposalMethod::DoNotDispose,
+                DisposalMethod::RestoreToPrevious,
+                DisposalMethod::RestoreToBackgroundColor,
+                DisposalMethod::DoNotDispose,
+            ]
+        );
+        assert_eq!(cropped_frames[1].left, 1);
+        assert_eq!(cropped_frames[1].image, image(&[&[BLUE, BLUE]]));
+        assert_eq!(cropped_frames[3].image, image(&[&[CLEAR]]));
+        assert_eq!(render(&cropped_frames, 4, 1), frames);
+    }
+
+    #[test]
+    fn should_enlarge_rectangle_to_clear() {
+        let frames = [
+            image(&[&[RED, RED, RED]]),
+            image(&[&[RED, BLUE, RED]]),
+            image(&[&[CLEAR, CLEAR, CLEAR]]),
+        ];
+        let cropped_frames = crop_frames(&frames);
+
+        assert_eq!(cropped_frames[1].left, 0);
+        assert_eq!(cropped_frames[1].image, frames[1]);
+        assert_eq!(
+            cropped_frames[1].disposal_method,
+            DisposalMethod::RestoreToBackgroundColor
+        );
+        assert_eq!(render(&cropped_frames, 3, 1), frames);
+    }
+
+    /// Noise made of 5 colors, which compresses poorly.
+    fn noise() -> RgbaImage {
+        let colors = [
+            RED,
+            BLUE,
+            [0, 255, 0, 255],
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+        ];
+        let mut image = RgbaImage::new(16, 16);
+        let mut state = 1u32;
+        for pixel in image.pixels.chunks_exact_mut(4) {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            pixel.copy_from_slice(&colors[(state >> 16) as usize % 5]);
+        }
+        image
+    }
+
+    #[test]
+    fn should_make_unchanged_pixels_transparent() {
+        let canvas = noise();
+        let mut changed = canvas.clone();
+        for &(x, y) in &[(1, 1), (14, 14)] {
+            let i = (y * 16 + x) * 4;
+            changed.pixels[i..i + 4].copy_from_slice(&[128, 128, 128, 255]);
+        }
+        let frames = [canvas, changed];
+
+        let quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+        let (global_color_table, frames_with_transparency) =
+            optimize_animation(&frames, &quantizer, 0.0);
+        assert_eq!(frames_with_transparency[0].transparent_color_index, None);
+        let second = &frames_with_transparency[1];
+        assert_eq!((second.width, second.height), (14, 14));
+        let transparent_color_index = second.transparent_color_index.unwrap();
+        assert_eq!(
+            second
+                .indices
+                .iter()
+                .filter(|&&index| index != transparent_color_index)
+                .count(),
+            2
+        );
+
+        let mut encoder = Encoder::new(16, 16, Some(&global_color_table)).unwrap();
+        for frame in &frames_with_transparency {
+            encoder.add_frame(frame).unwrap();
+        }
+        let gif = encoder.gif();
+        let rendered: Vec<_> = Renderer::new(&gif)
+            .map(|frame| frame.unwrap().image)
+            .collect();
+        assert_eq!(rendered, frames);
+    }
+
+    #[test]
+    fn should_keep_frame_when_transparency_does_not_help() {
+        let canvas = RgbaImage::new(2, 1);
+        let mut frame = IndexedFrame::new(0, 0, 2, 1, 0);
+        let color_table = [255, 0, 0, 0, 0, 255];
+        assert!(!make_unchanged_pixels_transparent(
+            &mut frame,
+            &canvas,
+            Some(&color_table)
+        ));
+        assert_eq!(frame, IndexedFrame::new(0, 0, 2, 1, 0));
+    }
+}
@@ -0,0 +1,464 @@
+use super::super::encoder::{EncodedImage, OwnedBlock};
+use super::super::lzw::{decode, encode_with_strategy, minimum_code_size, ClearStrategy};
+use super::super::model::{
+    Block, DisposalMethod, GIFVersion, GraphicControlExtension, ImageDescriptor, GIF,
+};
+use super::super::render::{Compositor, RgbaImage};
+use super::super::timing::DelayPolicy;
+use super::super::writer::write_gif;
+use std::io::{Result as IOResult, Write};
+
+const CLEAR_STRATEGIES: [ClearStrategy; 3] = [
+    ClearStrategy::WhenFull,
+    ClearStrategy::Never,
+    ClearStrategy::OnRatioDrop,
+];
+
+/// Shrinks existing GIFs without changing a single displayed pixel.
+///
+/// Every image is decoded and compressed again with the clear code placement that gives the
+/// smallest data, and Local Color Tables are dropped when the Global Color Table has all the colors
+/// they use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GIFOptimizer {
+    // Whether Comment Extensions are dropped
+    pub strip_comments: bool,
+    // Whether frames that show exactly the same canvas as the previous one are dropped, their
+    // delay being added to that frame
+    pub merge_duplicate_frames: bool,
+}
+
+/// The result of `GIFOptimizer::optimize`. Like `Encoder`, it owns the re-encoded image data and
+/// lends it out as a `GIF`.
+pub struct OptimizedGIF<'a> {
+    // The original GIF without its blocks
+    header: GIF<'a>,
+    blocks: Vec<OwnedBlock<'a>>,
+}
+
+impl<'a> OptimizedGIF<'a> {
+    pub fn gif(&self) -> GIF<'_> {
+        let mut gif = GIF {
+            data: self.blocks.iter().map(OwnedBlock::block).collect(),
+            ..self.header.clone()
+        };
+        // merging frames may add a Graphic Control Extension
+        if gif.required_version() == GIFVersion::GIF89a {
+            gif.version = GIFVersion::GIF89a;
+        }
+        gif
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> IOResult<()> {
+        write_gif(&self.gif(), writer)
+    }
+}
+
+/// Finds the frames that leave the canvas exactly as the previous frame did, both when they are
+/// displayed and once they are disposed of. Returns whether each block is dropped, and the new
+/// delay of each frame that absorbs the following ones.
+///
+/// Frames are only merged when that keeps the total delay the same in browsers, and merging stops
+/// at the first frame that cannot be decoded.
+fn find_duplicate_frames(gif: &GIF<'_>) -> (Vec<bool>, Vec<Option<u16>>) {
+    let mut dropped = vec![false; gif.data.len()];
+    let mut delays = vec![None; gif.data.len()];
+
+    let mut compositor = Compositor::new(gif);
+    // The last kept frame, with its delay so far and the canvas it leaves once disposed of
+    let mut kept: Option<(usize, u16, RgbaImage, RgbaImage)> = None;
+    while let Some(Ok(frame)) = compositor.next_frame(gif) {
+        let disposed_canvas = compositor.disposed_canvas();
+        if let Some((block, delay_time, image, kept_disposed_canvas)) = &mut kept {
+            let merged_delay = delay_time.checked_add(frame.delay_time).filter(|&merged| {
+                let browser_delay =
+                    |delay_time| u32::from(DelayPolicy::Browser.normalize(delay_time));
+                browser_delay(*delay_time) + browser_delay(frame.delay_time)
+                    == browser_delay(merged)
+            });
+            if let Some(merged_delay) = merged_delay {
+                if *image == frame.image && *kept_disposed_canvas == disposed_canvas {
+                    dropped[frame.block] = true;
+                    delays[*block] = Some(merged_delay);
+                    *delay_time = merged_delay;
+                    continue;
+                }
+            }
+        }
+        kept = Some((frame.block, frame.delay_time, frame.image, disposed_canvas));
+    }
+
+    (dropped, delays)
+}
+
+/// Maps indices into a Local Color Table to indices into the Global Color Table with the same
+/// colors, transparent pixels getting an index that no opaque pixel uses. Returns the new indices
+/// and transparent color index, or `None` if the Global Color Table lacks a color.
+fn remap(
+    indices: &[u8],
+    local_color_table: &[u8],
+    transparent_color_index: Option<u8>,
+    global_color_table: &[u8],
+) -> Option<(Vec<u8>, Option<u8>)> {
+    let mut used = [false; 256];
+    for &index in indices {
+        used[index as usize] = true;
+    }
+
+    let mut mapping = [0u8; 256];
+    let mut taken = [false; 256];
+    for index in 0..256 {
+        if !used[index] || Some(index as u8) == transparent_color_index {
+            continue;
+        }
+        // indices outside of the table show as transparent, which is left to the original
+        let color = local_color_table.get(3 * index..3 * index + 3)?;
+        let global_index = global_color_table
+            .chunks_exact(3)
+            .take(256)
+            .position(|global_color| global_color == color)?;
+        mapping[index] = global_index as u8;
+        taken[global_index] = true;
+    }
+
+    let new_transparent_color_index = match transparent_color_index {
+        Some(transparent_color_index) => {
+            let colors = (global_color_table.len() / 3).min(256);
+            let new_index = (0..colors).find(|&index| !taken[index])? as u8;
+            mapping[transparent_color_index as usize] = new_index;
+            Some(new_index)
+        }
+        None => None,
+    };
+
+    Some((
+        indices
+            .iter()
+            .map(|&index| mapping[index as usize])
+            .collect(),
+        new_transparent_color_index,
+    ))
+}
+
+/// Compresses the image of a graphic block again in the smallest way found, with and without its
+/// Local Color Table. `None` when the original is as small, or when its data cannot be decoded or
+/// is cut short, since the pixels it leaves out must stay as they are.
+fn reencode(block: &Block<'_>, global_color_table: Option<&[u8]>) -> Option<EncodedImage> {
+    let (graphic_control_extension, image_descriptor, local_color_table, image_data) = match block {
+        Block::GraphicBlock {
+            graphic_control_extension,
+            image_descriptor,
+            local_color_table,
+            image_data,
+        } => (
+            graphic_control_extension,
+            image_descriptor,
+            local_color_table,
+            image_data,
+        ),
+        _ => return None,
+    };
+
+    // the indices in the order in which they are stored, like `decode_indices` before
+    // deinterlacing
+    let pixels = image_descriptor.width as usize * image_descriptor.height as usize;
+    let indices = decode(image_data.lzw_minimum_code_size, image_data.data, pixels).ok()?;
+    if indices.len() < pixels {
+        return None;
+    }
+    let original_size = image_data.data.0.len() + local_color_table.map_or(0, <[u8]>::len);
+
+    let mut candidates = vec![(
+        *graphic_control_extension,
+        *image_descriptor,
+        local_color_table.map(<[u8]>::to_vec),
+        indices.clone(),
+    )];
+    if let (Some(local_color_table), Some(global_color_table)) =
+        (local_color_table, global_color_table)
+    {
+        let transparent_color_index = graphic_control_extension
+            .filter(|graphic_control_extension| graphic_control_extension.transparent_color_flag())
+            .map(|graphic_control_extension| graphic_control_extension.transparent_color_index);
+        if let Some((remapped, new_transparent_color_index)) = remap(
+            &indices,
+            local_color_table,
+            transparent_color_index,
+            global_color_table,
+        ) {
+            let mut graphic_control_extension = *graphic_control_extension;
+            if let (Some(graphic_control_extension), Some(new_transparent_color_index)) =
+                (&mut graphic_control_extension, new_transparent_color_index)
+            {
+                graphic_control_extension.transparent_color_index = new_transparent_color_index;
+            }
+            candidates.push((
+                graphic_control_extension,
+                ImageDescriptor {
+                    // without the Local Color Table flag, sort flag and size
+                    packed_field: image_descriptor.packed_field & 0b_0101_1000,
+                    ..*image_descriptor
+                },
+                None,
+                remapped,
+            ));
+        }
+    }
+
+    let mut best: Option<EncodedImage> = None;
+    for (graphic_control_extension, image_descriptor, local_color_table, indices) in candidates {
+        let colors = indices.iter().max().map_or(0, |&index| index as usize + 1);
+        let lzw_minimum_code_size = minimum_code_size(colors);
+        let local_color_table_size = local_color_table.as_ref().map_or(0, Vec::len);
+        for &clear_strategy in &CLEAR_STRATEGIES {
+            let data = encode_with_strategy(lzw_minimum_code_size, &indices, clear_strategy);
+            let best_size = best.as_ref().map_or(original_size, |image| {
+                image.data.len() + image.local_color_table.as_ref().map_or(0, Vec::len)
+            });
+            if data.len() + local_color_table_size < best_size {
+                best = Some(EncodedImage {
+                    graphic_control_extension,
+                    image_descriptor,
+                    local_color_table: local_color_table.clone(),
+                    lzw_minimum_code_size,
+                    data,
+                });
+            }
+        }
+    }
+    best
+}
+
+impl GIFOptimizer {
+    /// An optimizer merging duplicate frames and keeping comments.
+    pub fn new() -> GIFOptimizer {
+        GIFOptimizer {
+            strip_comments: false,
+            merge_duplicate_frames: true,
+        }
+    }
+
+    /// Optimizes a GIF, keeping every block that cannot be made smaller as is.
+    pub fn optimize<'a>(&self, gif: &GIF<'a>) -> OptimizedGIF<'a> {
+        let (dropped, delays) = if self.merge_duplicate_frames {
+            find_duplicate_frames(gif)
+        } else {
+            (vec![false; gif.data.len()], vec![None; gif.data.len()])
+        };
+
+        let mut blocks = Vec::with_capacity(gif.data.len());
+        // stray Graphic Control Extensions are attached to the images they apply to, so that
+        // merging and transparency remapping see them
+        for (index, block) in gif.attached_blocks().into_iter().enumerate() {
+            let mut block = match block {
+                Some(block) if !dropped[index] => block,
+                _ => continue,
+            };
+            match &mut block {
+                Block::CommentExtension(_) if self.strip_comments => continue,
+                Block::GraphicBlock {
+                    graphic_control_extension,
+                    ..
+                } => {
+                    if let Some(delay_time) = delays[index] {
+                        graphic_control_extension
+                            .get_or_insert_with(|| {
+                                GraphicControlExtension::new(DisposalMethod::Unspecified, 0, None)
+                            })
+                            .delay_time = delay_time;
+                    }
+                }
+                _ => {}
+            }
+            blocks.push(match reencode(&block, gif.global_color_table) {
+                Some(image) => OwnedBlock::Encoded(image),
+                None => OwnedBlock::Borrowed(block),
+            });
+        }
+
+        OptimizedGIF {
+            header: GIF {
+                data: Vec::new(),
+                ..gif.clone()
+            },
+            blocks,
+        }
+    }
+}
+
+impl Default for GIFOptimizer {
+    fn default() -> GIFOptimizer {
+        GIFOptimizer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::encoder::{Encoder, IndexedFrame};
+    use super::super::super::frame::detach_graphic_control_extensions;
+    use super::super::super::model::SubBlocks;
+    use super::super::super::render::Renderer;
+    use super::*;
+
+    const COLORS: [u8; 9] = [0, 0, 0, 255, 0, 0, 0, 0, 255];
+
+    fn render(gif: &GIF<'_>) -> Vec<(u16, RgbaImage)> {
+        Renderer::new(gif)
+            .map(|frame| {
+                let frame = frame.unwrap();
+                (frame.delay_time, frame.image)
+            })
+            .collect()
+    }
+
+    fn frame(index: u8, delay_time: u16) -> IndexedFrame {
+        let mut frame = IndexedFrame::new(0, 0, 4, 4, index);
+        frame.delay_time = delay_time;
+        frame
+    }
+
+    #[test]
+    fn should_drop_local_color_table() {
+        let mut encoder = Encoder::new(4, 4, Some(&COLORS)).unwrap();
+        let mut frame = frame(0, 0);
+        // blue, red and a transparent color that is not in the Global Color Table
+        frame.local_color_table = Some(vec![0, 0, 255, 255, 0, 0, 9, 9, 9]);
+        frame.indices = vec![0, 1, 2, 1, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2];
+        frame.transparent_color_index = Some(2);
+        encoder.add_frame(&frame).unwrap();
+        let gif = encoder.gif();
+
+        let optimized = GIFOptimizer::new().optimize(&gif);
+        let optimized_gif = optimized.gif();
+        match optimized_gif.data[0] {
+            Block::GraphicBlock {
+                graphic_control_extension: Some(graphic_control_extension),
+                image_descriptor,
+                local_color_table,
+                ..
+            } => {
+                assert_eq!(local_color_table, None);
+                assert!(!image_descriptor.local_color_table_flag());
+                // the transparent color takes the only unused index
+                assert_eq!(graphic_control_extension.transparent_color_index, 0);
+            }
+            _ => panic!("expected a graphic block"),
+        }
+        assert_eq!(render(&optimized_gif), render(&gif));
+    }
+
+    #[test]
+    fn should_merge_duplicate_frames() {
+        let mut encoder = Encoder::new(4, 4, Some(&COLORS)).unwrap();
+        encoder.add_frame(&frame(1, 5)).unwrap();
+        encoder.add_frame(&frame(1, 7)).unwrap();
+        encoder.add_frame(&frame(2, 5)).unwrap();
+        // delays of 0 and 1 are shown as 10 by browsers, so these are not merged
+        encoder.add_frame(&frame(2, 1)).unwrap();
+        let gif = encoder.gif();
+
+        let optimized = GIFOptimizer::new().optimize(&gif);
+        let rendered = render(&optimized.gif());
+        let delays: Vec<_> = rendered.iter().map(|(delay_time, _)| *delay_time).collect();
+        assert_eq!(delays, vec![12, 5, 1]);
+        assert_eq!(rendered[0].1, render(&gif)[1].1);
+
+        let mut optimizer = GIFOptimizer::new();
+        optimizer.merge_duplicate_frames = false;
+        assert_eq!(render(&optimizer.optimize(&gif).gif()), render(&gif));
+    }
+
+    #[test]
+    fn should_merge_duplicate_frames_with_stray_graphic_control_extensions() {
+        let mut encoder = Encoder::new(4, 4, Some(&COLORS)).unwrap();
+        encoder.add_frame(&frame(1, 0)).unwrap();
+        // black, except for a transparent pixel through which the red frame shows
+        let mut transparent = frame(0, 20);
+        transparent.indices[1] = 2;
+        transparent.transparent_color_index = Some(2);
+        encoder.add_frame(&transparent).unwrap();
+        transparent.delay_time = 10;
+        encoder.add_frame(&transparent).unwrap();
+        let mut gif = encoder.gif();
+
+        detach_graphic_control_extensions(&mut gif);
+        let original = render(&gif);
+        let delays: Vec<_> = original.iter().map(|(delay_time, _)| *delay_time).collect();
+        assert_eq!(delays, vec![0, 20, 10]);
+        assert_eq!(original[1].1.pixel(1, 0), [255, 0, 0, 255]);
+
+        let optimized = GIFOptimizer::new().optimize(&gif);
+        let optimized_gif = optimized.gif();
+        assert!(!optimized_gif
+            .data
+            .iter()
+            .any(|block| matches!(block, Block::GraphicControlExtension(_))));
+        let rendered = render(&optimized_gif);
+        assert_eq!(
+            rendered,
+            vec![original[0].clone(), (30, original[1].1.clone())]
+        );
+    }
+
+    #[test]
+    fn should_strip_comments() {
+        let mut encoder = Encoder::new(4, 4, Some(&COLORS)).unwrap();
+        encoder.add_frame(&frame(1, 0)).unwrap();
+        let mut gif = encoder.gif();
+        let comment = [5, b'h', b'e', b'l', b'l', b'o', 0];
+        gif.data.push(Block::CommentExtension(SubBlocks(&comment)));
+
+        let mut optimizer = GIFOptimizer::new();
+        assert_eq!(optimizer.optimize(&gif).gif().data.len(), 2);
+        optimizer.strip_comments = true;
+        let optimized = optimizer.optimize(&gif);
+        assert_eq!(optimized.gif().data.len(), 1);
+        assert_eq!(render(&optimized.gif()), render(&gif));
+    }
+
+    #[test]
+    fn should_pick_smallest_clear_strategy() {
+        let block = |data| Block::GraphicBlock {
+            graphic_control_extension: None,
+            image_descriptor: ImageDescriptor {
+                left: 0,
+                top: 0,
+                width: 64,
+                height: 64,
+                packed_field: 0,
+            },
+            local_color_table: None,
+            image_data: super::super::super::model::ImageData {
+                lzw_minimum_code_size: 8,
+                data: SubBlocks(data),
+            },
+        };
+        // a single color, needlessly encoded with 8-bit indices
+        let data = encode_with_strategy(8, &[1; 64 * 64], ClearStrategy::WhenFull);
+        let image = reencode(&block(&data), Some(&COLORS)).unwrap();
+        assert_eq!(image.lzw_minimum_code_size, 2);
+        assert!(image.data.len() < data.len());
+        assert_eq!(reencode(&image.block(), Some(&COLORS)).map(|_| ()), None);
+    }
+
+    #[test]
+    fn should_keep_truncated_images() {
+        let data = encode_with_strategy(8, &[1; 64 * 32], ClearStrategy::WhenFull);
+        let block = Block::GraphicBlock {
+            graphic_control_extension: None,
+            image_descriptor: ImageDescriptor {
+                left: 0,
+                top: 0,
+                width: 64,
+                height: 64,
+                packed_field: 0,
+            },
+            local_color_table: None,
+            image_data: super::super::super::model::ImageData {
+                lzw_minimum_code_size: 8,
+                data: SubBlocks(&data),
+            },
+        };
+        assert_eq!(reencode(&block, Some(&COLORS)).map(|_| ()), None);
+    }
+}
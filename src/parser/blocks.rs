@@ -1,112 +1,155 @@
+use super::reader::{ParsingError, Reader};
 use super::{
     subblocks::data_subblocks, Block, GraphicControlExtension, ImageData, ImageDescriptor,
 };
-use nom::{le_u16, le_u8};
 
-named!(graphic_control_extension<&[u8], GraphicControlExtension>,
-       do_parse!(
-                                    tag!([0x21, 0xf9]) >>
-           byte_size:               le_u8              >>
-           packed_field:            le_u8              >>
-           delay_time:              le_u16             >>
-           transparent_color_index: le_u8              >>
-                                    tag!([0x00])       >>
-           (GraphicControlExtension {
-               byte_size: byte_size,
-               packed_field: packed_field,
-               delay_time: delay_time,
-               transparent_color_index: transparent_color_index,
-           })
-       )
-);
+const EXTENSION_INTRODUCER: u8 = 0x21;
+const IMAGE_SEPARATOR: u8 = 0x2c;
+const GRAPHIC_CONTROL_LABEL: u8 = 0xf9;
+const PLAIN_TEXT_LABEL: u8 = 0x01;
+const APPLICATION_LABEL: u8 = 0xff;
+const COMMENT_LABEL: u8 = 0xfe;
 
-named!(image_descriptor<&[u8], ImageDescriptor>,
-       do_parse!(
-                         tag!([0x2c]) >>
-           left:         le_u16       >>
-           top:          le_u16       >>
-           width:        le_u16       >>
-           height:       le_u16       >>
-           packed_field: le_u8        >>
-           (ImageDescriptor {
-                left: left,
-                top: top,
-                width: width,
-                height: height,
-                packed_field: packed_field,
-           })
-       )
-);
+fn graphic_control_extension(
+    reader: &mut Reader<'_>,
+) -> Result<GraphicControlExtension, ParsingError> {
+    const CONTEXT: &str = "Graphic Control Extension";
+    reader.tag(&[EXTENSION_INTRODUCER, GRAPHIC_CONTROL_LABEL], CONTEXT)?;
+    let byte_size = reader.u8(CONTEXT)?;
+    let packed_field = reader.u8(CONTEXT)?;
+    let delay_time = reader.u16(CONTEXT)?;
+    let transparent_color_index = reader.u8(CONTEXT)?;
+    reader.tag(&[0x00], CONTEXT)?;
+    Ok(GraphicControlExtension {
+        byte_size,
+        packed_field,
+        delay_time,
+        transparent_color_index,
+    })
+}
 
-named!(image_data<&[u8], ImageData>,
-       do_parse!(
-           lzw_minimum_code_size: le_u8          >>
-           data:                  data_subblocks >>
-           (ImageData {
-               lzw_minimum_code_size: lzw_minimum_code_size,
-               data: data
-           })
-        )
-);
+fn image_descriptor(reader: &mut Reader<'_>) -> Result<ImageDescriptor, ParsingError> {
+    const CONTEXT: &str = "Image Descriptor";
+    reader.tag(&[IMAGE_SEPARATOR], CONTEXT)?;
+    Ok(ImageDescriptor {
+        left: reader.u16(CONTEXT)?,
+        top: reader.u16(CONTEXT)?,
+        width: reader.u16(CONTEXT)?,
+        height: reader.u16(CONTEXT)?,
+        packed_field: reader.u8(CONTEXT)?,
+    })
+}
 
-named!(graphic_block<&[u8], Block>,
-       do_parse!(
-           graphic_control_extension: opt!(graphic_control_extension) >>
-           image_descriptor:          image_descriptor                >>
-           local_color_table:         cond!(
+fn image_data<'a>(reader: &mut Reader<'a>) -> Result<ImageData<'a>, ParsingError> {
+    const CONTEXT: &str = "Image Data";
+    Ok(ImageData {
+        lzw_minimum_code_size: reader.u8(CONTEXT)?,
+        data: data_subblocks(reader, CONTEXT)?,
+    })
+}
 
-               image_descriptor.packed_field & 0b_1000_0000 != 0,
-               take!(3 * (1 << ((image_descriptor.packed_field & 0b_0000_0111) + 1)))
-                                      )                               >>
-           image_data:                image_data                      >>
-           (Block::GraphicBlock {
-               graphic_control_extension: graphic_control_extension,
-               image_descriptor: image_descriptor,
-               local_color_table: local_color_table,
-               image_data: image_data
-           })
-       )
-);
+fn graphic_block<'a>(
+    reader: &mut Reader<'a>,
+    graphic_control_extension: Option<GraphicControlExtension>,
+) -> Result<Block<'a>, ParsingError> {
+    let image_descriptor = image_descriptor(reader)?;
+    let local_color_table =
+        reader.color_table(image_descriptor.packed_field, "Local Color Table")?;
+    Ok(Block::GraphicBlock {
+        graphic_control_extension,
+        image_descriptor,
+        local_color_table,
+        image_data: image_data(reader)?,
+    })
+}
 
-named!(plain_text_block<&[u8], Block>,
-       do_parse!(
-           graphic_control_extension: opt!(graphic_control_extension) >>
-                                      tag!([0x21, 0x01])              >>
-           text:                      data_subblocks                  >>
-           (Block::TextBlock {
-               graphic_control_extension: graphic_control_extension,
-               text: text
-           })
-       )
-);
+fn plain_text_block<'a>(
+    reader: &mut Reader<'a>,
+    graphic_control_extension: Option<GraphicControlExtension>,
+) -> Result<Block<'a>, ParsingError> {
+    const CONTEXT: &str = "Plain Text Extension";
+    reader.tag(&[EXTENSION_INTRODUCER, PLAIN_TEXT_LABEL], CONTEXT)?;
+    Ok(Block::TextBlock {
+        graphic_control_extension,
+        text: data_subblocks(reader, CONTEXT)?,
+    })
+}
 
-named!(application_extension<&[u8], Block>,
-       do_parse!(
-                 tag!([0x21, 0xff]) >>
-           data: data_subblocks     >>
-           (Block::ApplicationExtension(data))
-       )
-);
+fn application_extension<'a>(reader: &mut Reader<'a>) -> Result<Block<'a>, ParsingError> {
+    const CONTEXT: &str = "Application Extension";
+    reader.tag(&[EXTENSION_INTRODUCER, APPLICATION_LABEL], CONTEXT)?;
+    Ok(Block::ApplicationExtension(data_subblocks(
+        reader, CONTEXT,
+    )?))
+}
+
+fn comment_extension<'a>(reader: &mut Reader<'a>) -> Result<Block<'a>, ParsingError> {
+    const CONTEXT: &str = "Comment Extension";
+    reader.tag(&[EXTENSION_INTRODUCER, COMMENT_LABEL], CONTEXT)?;
+    Ok(Block::CommentExtension(data_subblocks(reader, CONTEXT)?))
+}
 
-named!(comment_extension<&[u8], Block>,
-       do_parse!(
-                 tag!([0x21, 0xfe]) >>
-           data: data_subblocks     >>
-           (Block::CommentExtension(data))
-       )
-);
+fn unknown_extension<'a>(reader: &mut Reader<'a>) -> Result<Block<'a>, ParsingError> {
+    const CONTEXT: &str = "extension";
+    reader.tag(&[EXTENSION_INTRODUCER], CONTEXT)?;
+    let position = reader.position();
+    let label = reader.u8(CONTEXT)?;
+    if matches!(
+        label,
+        GRAPHIC_CONTROL_LABEL | PLAIN_TEXT_LABEL | APPLICATION_LABEL | COMMENT_LABEL
+    ) {
+        return Err(reader.invalid(position, CONTEXT));
+    }
+    Ok(Block::UnknownExtension {
+        label,
+        data: data_subblocks(reader, CONTEXT)?,
+    })
+}
 
-named!(pub block<&[u8], Block>, alt!(graphic_block | plain_text_block | application_extension | comment_extension));
+/// Reads the next block, telling the kind of block from its first bytes.
+///
+/// A Graphic Control Extension belongs to an image or a Plain Text Extension right after it, and
+/// is stray otherwise, so the byte after it must be available.
+pub fn block<'a>(reader: &mut Reader<'a>) -> Result<Block<'a>, ParsingError> {
+    match (reader.peek(0), reader.peek(1)) {
+        (Some(IMAGE_SEPARATOR), _) => graphic_block(reader, None),
+        (Some(EXTENSION_INTRODUCER), Some(GRAPHIC_CONTROL_LABEL)) => {
+            let graphic_control_extension = graphic_control_extension(reader)?;
+            match (reader.peek(0), reader.peek(1)) {
+                (Some(IMAGE_SEPARATOR), _) => {
+                    graphic_block(reader, Some(graphic_control_extension))
+                }
+                (Some(EXTENSION_INTRODUCER), Some(PLAIN_TEXT_LABEL)) => {
+                    plain_text_block(reader, Some(graphic_control_extension))
+                }
+                (None, _) | (Some(EXTENSION_INTRODUCER), None) => {
+                    Err(reader.incomplete(1, "block"))
+                }
+                _ => Ok(Block::GraphicControlExtension(graphic_control_extension)),
+            }
+        }
+        (Some(EXTENSION_INTRODUCER), Some(PLAIN_TEXT_LABEL)) => plain_text_block(reader, None),
+        (Some(EXTENSION_INTRODUCER), Some(APPLICATION_LABEL)) => application_extension(reader),
+        (Some(EXTENSION_INTRODUCER), Some(COMMENT_LABEL)) => comment_extension(reader),
+        (Some(EXTENSION_INTRODUCER), _) => unknown_extension(reader),
+        (None, _) => Err(reader.incomplete(1, "block")),
+        (Some(_), _) => Err(reader.invalid(reader.position(), "block")),
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use super::super::reader::{parse, ParsingErrorKind};
     use super::super::SubBlocks;
     use super::*;
 
     #[test]
     fn should_parse_graphic_control_extension() {
         assert_eq!(
-            graphic_control_extension(&[0x21, 0xf9, 0x04, 0x01, 0x64, 0x00, 0x02, 0x00][..]),
+            parse(
+                &[0x21, 0xf9, 0x04, 0x01, 0x64, 0x00, 0x02, 0x00][..],
+                graphic_control_extension
+            ),
             Ok((
                 &b""[..],
                 GraphicControlExtension {
@@ -122,7 +165,10 @@ mod tests {
     #[test]
     fn should_read_image_descriptor() {
         assert_eq!(
-            image_descriptor(&[0x2c, 0x01, 0x00, 0x02, 0x00, 0x05, 0x00, 0x06, 0x00, 0x81][..]),
+            parse(
+                &[0x2c, 0x01, 0x00, 0x02, 0x00, 0x05, 0x00, 0x06, 0x00, 0x81][..],
+                image_descriptor
+            ),
             Ok((
                 &b""[..],
                 ImageDescriptor {
@@ -140,7 +186,7 @@ mod tests {
     fn should_parse_image_data() {
         let data = [1, 2, 255, 255, 3, 255, 255, 255, 0];
         assert_eq!(
-            image_data(&data[..]),
+            parse(&data[..], image_data),
             Ok((
                 &b""[..],
                 ImageData {
@@ -152,7 +198,7 @@ mod tests {
 
         let data = [3, 2, 255, 255, 3, 255, 255, 255, 0, 1, 2, 3];
         assert_eq!(
-            image_data(&data[..]),
+            parse(&data[..], image_data),
             Ok((
                 &[1, 2, 3][..],
                 ImageData {
@@ -185,7 +231,7 @@ mod tests {
             0xfa, 0xa8, 0xde, 0x60, 0x8c, 0x04, 0x91, 0x4c, 0x01, 0x00,
         ];
         assert_eq!(
-            graphic_block(&data[..]),
+            parse(&data[..], block),
             Ok((
                 &[][..],
                 Block::GraphicBlock {
@@ -225,7 +271,7 @@ mod tests {
             0x00, 0x0B, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x20, 0x77, 0x6F, 0x72, 0x6C, 0x64, 0x00,
         ];
         assert_eq!(
-            plain_text_block(&data[..]),
+            parse(&data[..], |reader| plain_text_block(reader, None)),
             Ok((
                 &[][..],
                 Block::TextBlock {
@@ -243,7 +289,7 @@ mod tests {
             0x03, 0x01, 0x05, 0x00, 0x00,
         ];
         assert_eq!(
-            application_extension(&data[..]),
+            parse(&data[..], application_extension),
             Ok((&[][..], Block::ApplicationExtension(SubBlocks(&data[2..]))))
         );
     }
@@ -254,8 +300,121 @@ mod tests {
             0x21, 0xFE, 0x09, 0x62, 0x6C, 0x75, 0x65, 0x62, 0x65, 0x72, 0x72, 0x79, 0x00,
         ];
         assert_eq!(
-            comment_extension(&data[..]),
+            parse(&data[..], comment_extension),
             Ok((&[][..], Block::CommentExtension(SubBlocks(&data[2..]))))
         );
     }
+
+    #[test]
+    fn should_parse_unknown_extension() {
+        let data = [0x21, 0x99, 0x03, 0x01, 0x02, 0x03, 0x00];
+        assert_eq!(
+            parse(&data[..], unknown_extension),
+            Ok((
+                &[][..],
+                Block::UnknownExtension {
+                    label: 0x99,
+                    data: SubBlocks(&data[2..])
+                }
+            ))
+        );
+        assert_eq!(
+            parse(&data[..], block),
+            Ok((
+                &[][..],
+                Block::UnknownExtension {
+                    label: 0x99,
+                    data: SubBlocks(&data[2..])
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn should_parse_stray_graphic_control_extension() {
+        let data = [
+            0x21, 0xf9, 0x04, 0x01, 0x64, 0x00, 0x02, 0x00, // graphic control extension
+            0x21, 0xfe, 0x01, 0x61, 0x00, // comment extension
+        ];
+        assert_eq!(
+            parse(&data[..], block),
+            Ok((
+                &data[8..],
+                Block::GraphicControlExtension(GraphicControlExtension {
+                    byte_size: 4,
+                    packed_field: 0x01,
+                    delay_time: 100,
+                    transparent_color_index: 2,
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn should_not_parse_known_extension_as_unknown() {
+        let data = [
+            0x21, 0xFE, 0x09, 0x62, 0x6C, 0x75, 0x65, 0x62, 0x65, 0x72, 0x72, 0x79, 0x00,
+        ];
+        assert!(parse(&data[..], unknown_extension).is_err());
+    }
+
+    #[test]
+    fn should_report_incomplete_blocks() {
+        // an image cut within its data
+        let data = [
+            0x21, 0xf9, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, // graphic control extension
+            0x2c, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, // image descriptor
+            0x02, 0x02, 0x44, // image data
+        ];
+        assert_eq!(
+            parse(&data[..], block),
+            Err(ParsingError {
+                offset: 21,
+                context: "Image Data",
+                kind: ParsingErrorKind::Incomplete(1),
+            })
+        );
+
+        // whether the Graphic Control Extension is stray depends on the next block
+        assert_eq!(
+            parse(&data[..8], block),
+            Err(ParsingError {
+                offset: 8,
+                context: "block",
+                kind: ParsingErrorKind::Incomplete(1),
+            })
+        );
+        assert_eq!(
+            parse(&[][..], block),
+            Err(ParsingError {
+                offset: 0,
+                context: "block",
+                kind: ParsingErrorKind::Incomplete(1),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_invalid_blocks() {
+        // the trailer
+        assert_eq!(
+            parse(&[0x3b][..], block),
+            Err(ParsingError {
+                offset: 0,
+                context: "block",
+                kind: ParsingErrorKind::Invalid,
+            })
+        );
+
+        // a Graphic Control Extension without its Block Terminator
+        let data = [0x21, 0xf9, 0x04, 0x00, 0x00, 0x00, 0x00, 0x01];
+        assert_eq!(
+            parse(&data[..], block),
+            Err(ParsingError {
+                offset: 7,
+                context: "Graphic Control Extension",
+                kind: ParsingErrorKind::Invalid,
+            })
+        );
+    }
 }
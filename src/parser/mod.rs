@@ -1,74 +1,186 @@
 mod subblocks;
 mod blocks;
+mod reader;
 
 use super::model::{
     Block, GraphicControlExtension, ImageData, ImageDescriptor, SubBlocks, GIF,
     GIFVersion
 };
-use nom::{le_u16, le_u8};
+use self::reader::{parse, Reader};
+use std::ops::Range;
 use std::result::Result;
 
-fn get_version(bytes: &[u8]) -> Result<GIFVersion, ()> {
-    match bytes {
+pub use self::reader::{ParsingError, ParsingErrorKind};
+
+const TRAILER: u8 = 0x3b;
+
+fn version(reader: &mut Reader<'_>) -> Result<GIFVersion, ParsingError> {
+    const CONTEXT: &str = "version";
+    let position = reader.position();
+    match reader.take(3, CONTEXT)? {
         b"87a" => Ok(GIFVersion::GIF87a),
         b"89a" => Ok(GIFVersion::GIF89a),
-        _ => Err(()),
+        _ => Err(reader.invalid(position, CONTEXT)),
     }
 }
 
-named!(version<&[u8], GIFVersion>, map_res!(alt!(tag!("87a") | tag!("89a")), get_version));
-
-named!(gif<&[u8], GIF>,
-       do_parse!(
-                                    tag!("GIF")          >>
-           version:                 version              >>
-           width:                   le_u16               >>
-           height:                  le_u16               >>
-           packed_field:            le_u8                >>
-           _background_color_index: le_u8                >>
-           _pixel_aspect_ratio:     le_u8                >>
-           global_color_table:      cond!(
-               packed_field & 0b_1000_0000 != 0,
-               take!(3 * (1 << ((packed_field & 0b_0000_0111) + 1)))
-                                    )                    >>
-           data:                   many0!(blocks::block) >>          
-           (GIF {
-               version: version,
-               width: width,
-               height: height,
-               global_color_table: global_color_table,
-               data: data,
-           })
-       )
-);
-
-pub fn parse_gif(gif_data: &[u8]) -> Result<GIF, ()> {
-    match gif(gif_data) {
-        Ok((_, gif)) => Ok(gif),
-        Err(_) => Err(()),
+fn header<'a>(reader: &mut Reader<'a>) -> Result<GIF<'a>, ParsingError> {
+    const CONTEXT: &str = "Logical Screen Descriptor";
+    reader.tag(b"GIF", "signature")?;
+    let version = version(reader)?;
+    let width = reader.u16(CONTEXT)?;
+    let height = reader.u16(CONTEXT)?;
+    let packed_field = reader.u8(CONTEXT)?;
+    let background_color_index = reader.u8(CONTEXT)?;
+    let pixel_aspect_ratio = reader.u8(CONTEXT)?;
+    let global_color_table = reader.color_table(packed_field, "Global Color Table")?;
+    Ok(GIF {
+        version,
+        width,
+        height,
+        packed_field,
+        background_color_index,
+        pixel_aspect_ratio,
+        global_color_table,
+        data: Vec::new(),
+        trailer: false,
+    })
+}
+
+/// Parses the Header, the Logical Screen Descriptor and the Global Color Table, returning them
+/// as a GIF without blocks along with the number of bytes they take.
+///
+/// Along with `parse_block`, this reads a GIF as its data arrives, parsing again once more data
+/// is available whenever the error is incomplete.
+pub fn parse_header(gif_data: &[u8]) -> Result<(GIF<'_>, usize), ParsingError> {
+    let (rest, gif) = parse(gif_data, header)?;
+    Ok((gif, gif_data.len() - rest.len()))
+}
+
+/// Parses the block at the start of the data, returning it along with the number of bytes it
+/// takes. Offsets in errors are relative to the start of the data.
+///
+/// The Trailer is not a block, so the caller checks for its 0x3b byte first.
+pub fn parse_block(block_data: &[u8]) -> Result<(Block<'_>, usize), ParsingError> {
+    let (rest, block) = parse(block_data, blocks::block)?;
+    Ok((block, block_data.len() - rest.len()))
+}
+
+/// A GIF read as far as possible, with where its blocks come from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedGIF<'a> {
+    pub gif: GIF<'a>,
+    // The range of bytes each block was read from, in the order of `GIF::data`
+    pub ranges: Vec<Range<usize>>,
+    // Why parsing stopped before reaching the Trailer, if it did
+    pub error: Option<ParsingError>,
+}
+
+/// Parses a GIF along with the range of bytes each block was read from. Parsing stops at the
+/// first block that cannot be read, the GIF holding the blocks before it.
+///
+/// Only an unreadable Header or Logical Screen Descriptor is an error, as there is no GIF
+/// without them.
+pub fn parse_gif_with_ranges(gif_data: &[u8]) -> Result<ParsedGIF<'_>, ParsingError> {
+    let mut reader = Reader::new(gif_data);
+    let mut gif = header(&mut reader)?;
+    let mut ranges = Vec::new();
+    loop {
+        let start = reader.position();
+        match blocks::block(&mut reader) {
+            Ok(block) => {
+                ranges.push(start..reader.position());
+                gif.data.push(block);
+            }
+            Err(error) => {
+                gif.trailer = gif_data.get(start) == Some(&TRAILER);
+                let error = Some(error).filter(|_| !gif.trailer);
+                return Ok(ParsedGIF { gif, ranges, error });
+            }
+        }
     }
 }
 
+/// Parses a GIF, keeping the blocks before the first one that cannot be read like browsers do.
+/// `parse_gif_with_ranges` tells why parsing stopped.
+pub fn parse_gif(gif_data: &[u8]) -> Result<GIF<'_>, ParsingError> {
+    parse_gif_with_ranges(gif_data).map(|parsed| parsed.gif)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use nom::{Context::Code, Err::Error, ErrorKind};
 
     #[test]
     fn should_parse_version_89a() {
-        assert_eq!(version(&b"89a"[..]), Ok((&b""[..], GIFVersion::GIF89a)));
+        assert_eq!(parse(&b"89a"[..], version), Ok((&b""[..], GIFVersion::GIF89a)));
     }
 
     #[test]
     fn should_parse_version_87a() {
-        assert_eq!(version(&b"87a"[..]), Ok((&b""[..], GIFVersion::GIF87a)));
+        assert_eq!(parse(&b"87a"[..], version), Ok((&b""[..], GIFVersion::GIF87a)));
     }
 
     #[test]
     fn should_fail_on_unknown_version() {
         assert_eq!(
-            version(&b"12a"[..]),
-            Err(Error(Code(&b"12a"[..], ErrorKind::Alt)))
+            parse(&b"12a"[..], version),
+            Err(ParsingError {
+                offset: 0,
+                context: "version",
+                kind: ParsingErrorKind::Invalid,
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_header() {
+        let data = [
+            0x47, 0x49, 0x46, 0x38, 0x39, 0x61, // header
+            0x02, 0x00, 0x01, 0x00, 0x80, 0x01, 0x00, // logical screen descriptor
+            0x00, 0x00, 0x00, 0xff, 0xff, 0xff, // global color table
+            0x3b, // trailer
+        ];
+        let (gif, len) = parse_header(&data).unwrap();
+        assert_eq!(len, 19);
+        assert_eq!(gif.version, GIFVersion::GIF89a);
+        assert_eq!((gif.width, gif.height), (2, 1));
+        assert_eq!(gif.global_color_table, Some(&data[13..19]));
+
+        assert_eq!(
+            parse_header(&data[..16]),
+            Err(ParsingError {
+                offset: 16,
+                context: "Global Color Table",
+                kind: ParsingErrorKind::Incomplete(3),
+            })
+        );
+        assert_eq!(
+            parse_header(b"PNG"),
+            Err(ParsingError {
+                offset: 0,
+                context: "signature",
+                kind: ParsingErrorKind::Invalid,
+            })
+        );
+
+        let parsed = parse_gif_with_ranges(&data).unwrap();
+        assert!(parsed.gif.data.is_empty());
+        assert!(parsed.gif.trailer);
+        assert_eq!(parsed.error, None);
+    }
+
+    #[test]
+    fn should_parse_blocks_one_at_a_time() {
+        let data = [
+            0x21, 0xfe, 0x01, 0x61, 0x00, // comment extension
+            0x3b, // trailer
+        ];
+        assert_eq!(
+            parse_block(&data),
+            Ok((Block::CommentExtension(SubBlocks(&data[2..5])), 5))
         );
+        assert!(parse_block(&data[..4]).unwrap_err().is_incomplete());
+        assert!(!parse_block(&data[5..]).unwrap_err().is_incomplete());
     }
 }
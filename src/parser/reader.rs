@@ -0,0 +1,233 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParsingErrorKind {
+    // The data ends within the structure, at least this many more bytes being needed
+    Incomplete(usize),
+    // The structure cannot contain the byte at the offset
+    Invalid,
+}
+
+/// Where and why GIF data cannot be parsed.
+///
+/// An incomplete error means that the data is valid so far, so that parsing may be tried again
+/// once more data is available.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParsingError {
+    // Offset of the invalid byte, or the length of the data when it is incomplete
+    pub offset: usize,
+    // The structure being parsed, as named by the specification
+    pub context: &'static str,
+    pub kind: ParsingErrorKind,
+}
+
+impl ParsingError {
+    pub fn is_incomplete(&self) -> bool {
+        match self.kind {
+            ParsingErrorKind::Incomplete(_) => true,
+            ParsingErrorKind::Invalid => false,
+        }
+    }
+}
+
+impl fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ParsingErrorKind::Incomplete(needed) => write!(
+                f,
+                "{} ends at byte {}, at least {} more bytes needed",
+                self.context, self.offset, needed
+            ),
+            ParsingErrorKind::Invalid => {
+                write!(f, "invalid {} at byte {}", self.context, self.offset)
+            }
+        }
+    }
+}
+
+impl Error for ParsingError {}
+
+/// Reads the data one structure after the other, keeping track of the offset for errors.
+pub(super) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, position: 0 }
+    }
+
+    pub(super) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(super) fn rest(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    pub(super) fn peek(&self, ahead: usize) -> Option<u8> {
+        self.rest().get(ahead).copied()
+    }
+
+    pub(super) fn invalid(&self, offset: usize, context: &'static str) -> ParsingError {
+        ParsingError {
+            offset,
+            context,
+            kind: ParsingErrorKind::Invalid,
+        }
+    }
+
+    /// An error for data that ends before the given number of bytes.
+    pub(super) fn incomplete(&self, needed: usize, context: &'static str) -> ParsingError {
+        ParsingError {
+            offset: self.data.len(),
+            context,
+            kind: ParsingErrorKind::Incomplete(needed),
+        }
+    }
+
+    pub(super) fn take(
+        &mut self,
+        len: usize,
+        context: &'static str,
+    ) -> Result<&'a [u8], ParsingError> {
+        let rest = self.rest();
+        if rest.len() < len {
+            return Err(self.incomplete(len - rest.len(), context));
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+
+    pub(super) fn u8(&mut self, context: &'static str) -> Result<u8, ParsingError> {
+        Ok(self.take(1, context)?[0])
+    }
+
+    /// Reads a little-endian 16-bit integer.
+    pub(super) fn u16(&mut self, context: &'static str) -> Result<u16, ParsingError> {
+        let bytes = self.take(2, context)?;
+        Ok(u16::from(bytes[0]) | u16::from(bytes[1]) << 8)
+    }
+
+    /// Reads the given bytes, failing on the first byte that differs even if the data is
+    /// incomplete.
+    pub(super) fn tag(&mut self, tag: &[u8], context: &'static str) -> Result<(), ParsingError> {
+        let rest = self.rest();
+        if let Some(mismatch) = tag
+            .iter()
+            .zip(rest)
+            .position(|(expected, byte)| expected != byte)
+        {
+            return Err(self.invalid(self.position + mismatch, context));
+        }
+        self.take(tag.len(), context).map(|_| ())
+    }
+
+    /// Reads the color table that a packed field of a Logical Screen Descriptor or an Image
+    /// Descriptor announces, if any.
+    pub(super) fn color_table(
+        &mut self,
+        packed_field: u8,
+        context: &'static str,
+    ) -> Result<Option<&'a [u8]>, ParsingError> {
+        if packed_field & 0b_1000_0000 == 0 {
+            return Ok(None);
+        }
+        let len = 3 * (1 << ((packed_field & 0b_0000_0111) + 1));
+        self.take(len, context).map(Some)
+    }
+}
+
+/// Runs a parser from the start of the data, returning the rest of the data along with the
+/// parsed value.
+pub(super) fn parse<'a, T>(
+    data: &'a [u8],
+    parser: impl FnOnce(&mut Reader<'a>) -> Result<T, ParsingError>,
+) -> Result<(&'a [u8], T), ParsingError> {
+    let mut reader = Reader::new(data);
+    let value = parser(&mut reader)?;
+    Ok((reader.rest(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_integers() {
+        let mut reader = Reader::new(&[0x01, 0x02, 0x03]);
+        assert_eq!(reader.u8("test"), Ok(1));
+        assert_eq!(reader.u16("test"), Ok(0x0302));
+        assert_eq!(reader.position(), 3);
+        assert_eq!(
+            reader.u16("test"),
+            Err(ParsingError {
+                offset: 3,
+                context: "test",
+                kind: ParsingErrorKind::Incomplete(2),
+            })
+        );
+    }
+
+    #[test]
+    fn should_read_tags() {
+        assert_eq!(
+            parse(&[0x21, 0xf9, 0x04], |reader| reader
+                .tag(&[0x21, 0xf9], "test")),
+            Ok((&[0x04][..], ()))
+        );
+
+        let error = parse(&[0x21, 0xfe], |reader| reader.tag(&[0x21, 0xf9], "test")).unwrap_err();
+        assert_eq!(error.offset, 1);
+        assert_eq!(error.kind, ParsingErrorKind::Invalid);
+        assert!(!error.is_incomplete());
+
+        // a mismatching byte is invalid even when the data is incomplete
+        let error = parse(&[0x47, 0x49], |reader| reader.tag(b"GIF", "test")).unwrap_err();
+        assert_eq!(error.kind, ParsingErrorKind::Incomplete(1));
+        assert!(error.is_incomplete());
+        let error = parse(&[0x47, 0x48], |reader| reader.tag(b"GIF", "test")).unwrap_err();
+        assert_eq!(error.kind, ParsingErrorKind::Invalid);
+    }
+
+    #[test]
+    fn should_read_color_tables() {
+        let data = [1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(
+            parse(&data, |reader| reader.color_table(0b_0000_0000, "test")),
+            Ok((&data[..], None))
+        );
+        assert_eq!(
+            parse(&data, |reader| reader.color_table(0b_1000_0000, "test")),
+            Ok((&data[6..], Some(&data[..6])))
+        );
+        assert_eq!(
+            parse(&data, |reader| reader.color_table(0b_1000_0001, "test")),
+            Err(ParsingError {
+                offset: 7,
+                context: "test",
+                kind: ParsingErrorKind::Incomplete(5),
+            })
+        );
+    }
+
+    #[test]
+    fn should_describe_errors() {
+        let error = ParsingError {
+            offset: 12,
+            context: "Image Data",
+            kind: ParsingErrorKind::Incomplete(3),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Image Data ends at byte 12, at least 3 more bytes needed"
+        );
+        let error = ParsingError {
+            kind: ParsingErrorKind::Invalid,
+            ..error
+        };
+        assert_eq!(error.to_string(), "invalid Image Data at byte 12");
+    }
+}
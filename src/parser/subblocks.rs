@@ -1,27 +1,31 @@
+use super::reader::{ParsingError, Reader};
 use super::SubBlocks;
-use nom::{le_u8, Context::Code, Err::Error, ErrorKind, IResult};
 use std::iter::{IntoIterator, Iterator};
 
-fn non_empty_subblock(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    match le_u8(input) {
-        Ok((_, 0)) => Err(Error(Code(input, ErrorKind::Custom(0)))),
-        Ok((_, n)) => take!(input, n + 1),
-        Err(err) => Err(err),
+/// Reads a sub-block along with its size byte, or `None` for the Block Terminator.
+fn non_empty_subblock<'a>(
+    reader: &mut Reader<'a>,
+    context: &'static str,
+) -> Result<Option<&'a [u8]>, ParsingError> {
+    let data = reader.rest();
+    match reader.u8(context)? {
+        0 => Ok(None),
+        n => {
+            reader.take(usize::from(n), context)?;
+            Ok(Some(&data[..=usize::from(n)]))
+        }
     }
 }
 
-pub fn data_subblocks(input: &[u8]) -> IResult<&[u8], SubBlocks> {
-    let mut i = 0;
-
-    while let Ok((_, subblock)) = non_empty_subblock(&input[i..]) {
-        i += subblock.len();
-    }
-
-    // the index should point to the terminating 0
-    match input[i] {
-        0 => map!(input, take!(i + 1), |data: &[u8]| SubBlocks(data)),
-        _ => Err(Error(Code(input, ErrorKind::Custom(0)))),
-    }
+/// Reads sub-blocks up to and including the Block Terminator.
+pub fn data_subblocks<'a>(
+    reader: &mut Reader<'a>,
+    context: &'static str,
+) -> Result<SubBlocks<'a>, ParsingError> {
+    let data = reader.rest();
+    let start = reader.position();
+    while non_empty_subblock(reader, context)?.is_some() {}
+    Ok(SubBlocks(&data[..reader.position() - start]))
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -68,62 +72,102 @@ impl<'a> IntoIterator for SubBlocks<'a> {
     }
 }
 
+/// Iterates over the data of each sub-block, without the size bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SubBlockSlices<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> Iterator for SubBlockSlices<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        match self.remaining.first() {
+            None | Some(0) => None,
+            Some(&len) => {
+                let (subblock, remaining) = self.remaining[1..].split_at(len as usize);
+                self.remaining = remaining;
+                Some(subblock)
+            }
+        }
+    }
+}
+
+impl<'a> SubBlocks<'a> {
+    pub fn slices(self) -> SubBlockSlices<'a> {
+        SubBlockSlices { remaining: self.0 }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::super::reader::{parse, ParsingErrorKind};
     use super::*;
-    use nom::{Err::Incomplete, Needed};
 
     #[test]
     fn should_parse_non_empty_subblock() {
-        let data = &[0][..];
+        let subblock = |data| parse(data, |reader| non_empty_subblock(reader, "test"));
+
+        assert_eq!(subblock(&[0][..]), Ok((&[][..], None)));
+        assert_eq!(subblock(&[1, 2, 3][..]), Ok((&[3][..], Some(&[1, 2][..]))));
         assert_eq!(
-            non_empty_subblock(data),
-            Err(Error(Code(data, ErrorKind::Custom(0))))
+            subblock(&[2, 3, 4, 5][..]),
+            Ok((&[5][..], Some(&[2, 3, 4][..])))
+        );
+        assert_eq!(
+            subblock(&[5][..]),
+            Err(ParsingError {
+                offset: 1,
+                context: "test",
+                kind: ParsingErrorKind::Incomplete(5),
+            })
         );
-
-        let data = &[1, 2, 3][..];
-        assert_eq!(non_empty_subblock(data), Ok((&[3][..], &[1, 2][..])));
-
-        let data = &[2, 3, 4, 5][..];
-        assert_eq!(non_empty_subblock(data), Ok((&[5][..], &[2, 3, 4][..])));
-
-        let data = &[5][..];
-        assert_eq!(non_empty_subblock(data), Err(Incomplete(Needed::Size(6))));
     }
 
     #[test]
     fn should_parse_data_subblocks() {
+        let subblocks = |data| parse(data, |reader| data_subblocks(reader, "test"));
+
         let data = &[0][..];
-        assert_eq!(data_subblocks(data), Ok((&[][..], SubBlocks(&[0][..]))));
+        assert_eq!(subblocks(data), Ok((&[][..], SubBlocks(&[0][..]))));
 
         let data = &[0, 1, 2, 3][..];
-        assert_eq!(
-            data_subblocks(data),
-            Ok((&[1, 2, 3][..], SubBlocks(&[0][..])))
-        );
+        assert_eq!(subblocks(data), Ok((&[1, 2, 3][..], SubBlocks(&[0][..]))));
 
         let data = &[1, 255, 0][..];
-        assert_eq!(
-            data_subblocks(data),
-            Ok((&[][..], SubBlocks(&[1, 255, 0][..])))
-        );
+        assert_eq!(subblocks(data), Ok((&[][..], SubBlocks(&[1, 255, 0][..]))));
 
         let data = &[1, 255, 2, 255, 255, 0][..];
         assert_eq!(
-            data_subblocks(data),
+            subblocks(data),
             Ok((&[][..], SubBlocks(&[1, 255, 2, 255, 255, 0][..])))
         );
 
         let data = &[2, 255, 255, 0, 1, 2, 3][..];
         assert_eq!(
-            data_subblocks(data),
+            subblocks(data),
             Ok((&[1, 2, 3][..], SubBlocks(&[2, 255, 255, 0][..])))
         );
 
         let data = &[5, 6][..];
         assert_eq!(
-            data_subblocks(data),
-            Err(Error(Code(data, ErrorKind::Custom(0))))
+            subblocks(data),
+            Err(ParsingError {
+                offset: 2,
+                context: "test",
+                kind: ParsingErrorKind::Incomplete(4),
+            })
+        );
+
+        // the Block Terminator is missing
+        let data = &[1, 255][..];
+        assert_eq!(
+            subblocks(data),
+            Err(ParsingError {
+                offset: 2,
+                context: "test",
+                kind: ParsingErrorKind::Incomplete(1),
+            })
         );
     }
 
@@ -140,4 +184,14 @@ mod tests {
         let result: Vec<u8> = subblocks.into_iter().collect();
         assert_eq!(result, vec![128, 129, 130]);
     }
+
+    #[test]
+    fn subblocks_should_be_iterable_by_slices() {
+        let subblocks = SubBlocks(&[2, 128, 129, 1, 130, 0][..]);
+        let result: Vec<&[u8]> = subblocks.slices().collect();
+        assert_eq!(result, vec![&[128, 129][..], &[130][..]]);
+
+        let subblocks = SubBlocks(&[0][..]);
+        assert_eq!(subblocks.slices().next(), None);
+    }
 }
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+/// Converts an sRGB color to Björn Ottosson's Oklab, where euclidean distances match perceived
+/// color differences much better than in RGB.
+fn oklab(color: [u8; 3]) -> [f32; 3] {
+    let linear = |value: u8| {
+        let value = f32::from(value) / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let [r, g, b] = [linear(color[0]), linear(color[1]), linear(color[2])];
+
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// The euclidean distance between Oklab colors.
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3)
+        .map(|channel| (a[channel] - b[channel]).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// The colors available to quantized pixels, looked up by perceptual distance.
+pub(crate) struct ColorTable {
+    colors: Vec<[u8; 3]>,
+    oklab: Vec<[f32; 3]>,
+    nearest_colors: HashMap<[u8; 3], u8>,
+}
+
+impl ColorTable {
+    pub(crate) fn new(colors: Vec<[u8; 3]>) -> ColorTable {
+        ColorTable {
+            oklab: colors.iter().cloned().map(oklab).collect(),
+            colors,
+            nearest_colors: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// The average distance in Oklab between opaque colors and their closest color in the table,
+    /// where 0.02 is about the smallest noticeable difference.
+    pub(crate) fn mean_error(&mut self, colors: &[Option<[u8; 3]>]) -> f32 {
+        let mut total = 0.0;
+        let mut count = 0;
+        for color in colors.iter().flatten() {
+            let index = self.nearest(*color);
+            total += distance(self.oklab[index as usize], oklab(*color));
+            count += 1;
+        }
+        if count == 0 {
+            0.0
+        } else {
+            total / count as f32
+        }
+    }
+
+    /// The distance in Oklab between two colors of the table.
+    pub(crate) fn distance(&self, a: u8, b: u8) -> f32 {
+        distance(self.oklab[a as usize], self.oklab[b as usize])
+    }
+
+    /// Index of the closest color by euclidean distance in Oklab.
+    pub(crate) fn nearest(&mut self, color: [u8; 3]) -> u8 {
+        let oklab_colors = &self.oklab;
+        *self.nearest_colors.entry(color).or_insert_with(|| {
+            let target = oklab(color);
+            let distance = |other: &[f32; 3]| -> f32 {
+                (0..3)
+                    .map(|channel| (other[channel] - target[channel]).powi(2))
+                    .sum()
+            };
+            oklab_colors
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+                .map_or(0, |(index, _)| index as u8)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_convert_to_oklab() {
+        let close = |a: [f32; 3], b: [f32; 3]| (0..3).all(|i| (a[i] - b[i]).abs() < 1e-3);
+        assert!(close(oklab([0, 0, 0]), [0.0, 0.0, 0.0]));
+        assert!(close(oklab([255, 255, 255]), [1.0, 0.0, 0.0]));
+        assert!(close(oklab([255, 0, 0]), [0.628, 0.225, 0.126]));
+    }
+
+    #[test]
+    fn should_find_perceptually_nearest_color() {
+        let mut color_table = ColorTable::new(vec![[0, 0, 0], [255, 255, 255], [255, 0, 0]]);
+        assert_eq!(color_table.nearest([200, 40, 40]), 2);
+        assert_eq!(color_table.nearest([200, 200, 200]), 1);
+        assert_eq!(color_table.nearest([20, 20, 20]), 0);
+        // mid gray is perceptually lighter than its RGB value suggests
+        let mut color_table = ColorTable::new(vec![[0, 0, 0], [255, 255, 255]]);
+        assert_eq!(color_table.nearest([110, 110, 110]), 1);
+    }
+
+    #[test]
+    fn should_measure_mean_error() {
+        let mut color_table = ColorTable::new(vec![[0, 0, 0], [255, 255, 255]]);
+        assert_eq!(color_table.mean_error(&[Some([0, 0, 0]), None]), 0.0);
+        let error = color_table.mean_error(&[Some([0, 0, 0]), Some([255, 0, 0])]);
+        assert!((error - 0.226).abs() < 1e-3, "{}", error);
+    }
+}
@@ -0,0 +1,157 @@
+use super::color_table::ColorTable;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dithering {
+    // Every pixel gets its nearest color, which bands gradients
+    None,
+    // Diffuses the whole error to 4 neighbours
+    FloydSteinberg,
+    // Diffuses 3/4 of the error to 6 neighbours, keeping more contrast
+    Atkinson,
+    // Ordered dithering with an 8x8 Bayer matrix, which is stable across animation frames
+    Bayer,
+}
+
+// Error diffusion kernels as (dx, dy, weight)
+const FLOYD_STEINBERG: [(isize, usize, f32); 4] = [
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+const ATKINSON: [(isize, usize, f32); 6] = [
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+fn clamp(color: [f32; 3]) -> [u8; 3] {
+    let mut clamped = [0; 3];
+    for (value, channel) in clamped.iter_mut().zip(&color) {
+        *value = channel.round().clamp(0.0, 255.0) as u8;
+    }
+    clamped
+}
+
+/// Maps colors to the indices of a color table, `None` standing for transparent pixels,
+/// which neither receive nor spread any error. The strength scales the error or the threshold
+/// added to each pixel, 0 disabling dithering and 1 being the usual amount.
+pub(super) fn map(
+    pixels: &[Option<[u8; 3]>],
+    width: usize,
+    color_table: &mut ColorTable,
+    dithering: Dithering,
+    strength: f32,
+) -> Vec<Option<u8>> {
+    let kernel: &[(isize, usize, f32)] = match dithering {
+        Dithering::FloydSteinberg => &FLOYD_STEINBERG,
+        Dithering::Atkinson => &ATKINSON,
+        Dithering::None | Dithering::Bayer => &[],
+    };
+    // Roughly the distance between neighbouring colors of an evenly spread color table
+    let spread = 255.0 / (color_table.colors().len() as f32).cbrt();
+
+    let mut errors = vec![[0.0f32; 3]; pixels.len()];
+    let mut indices = Vec::with_capacity(pixels.len());
+    for (position, pixel) in pixels.iter().enumerate() {
+        let color = match pixel {
+            Some(color) => color,
+            None => {
+                indices.push(None);
+                continue;
+            }
+        };
+        let (x, y) = (position % width, position / width);
+
+        let offset = match dithering {
+            Dithering::Bayer => {
+                let threshold = (f32::from(BAYER[y % 8][x % 8]) + 0.5) / 64.0 - 0.5;
+                [threshold * spread * strength; 3]
+            }
+            _ => errors[position],
+        };
+        let mut adjusted = [0.0; 3];
+        for channel in 0..3 {
+            adjusted[channel] = f32::from(color[channel]) + offset[channel];
+        }
+        let adjusted = clamp(adjusted);
+        let index = color_table.nearest(adjusted);
+        indices.push(Some(index));
+
+        let chosen = color_table.colors()[index as usize];
+        for &(dx, dy, weight) in kernel {
+            let neighbour_x = x as isize + dx;
+            if neighbour_x < 0 || neighbour_x as usize >= width {
+                continue;
+            }
+            let neighbour = (y + dy) * width + neighbour_x as usize;
+            if let Some(error) = errors.get_mut(neighbour) {
+                for channel in 0..3 {
+                    error[channel] += (f32::from(adjusted[channel]) - f32::from(chosen[channel]))
+                        * weight
+                        * strength;
+                }
+            }
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white_pixels(dithering: Dithering, strength: f32) -> usize {
+        let mut color_table = ColorTable::new(vec![[0, 0, 0], [255, 255, 255]]);
+        // a gray that is perceptually close to halfway between black and white
+        let pixels = vec![Some([118, 118, 118]); 16 * 16];
+        map(&pixels, 16, &mut color_table, dithering, strength)
+            .iter()
+            .filter(|&&index| index == Some(1))
+            .count()
+    }
+
+    #[test]
+    fn should_dither_between_colors() {
+        assert_eq!(white_pixels(Dithering::None, 1.0), 256);
+        assert_eq!(white_pixels(Dithering::FloydSteinberg, 0.0), 256);
+        assert_eq!(white_pixels(Dithering::Bayer, 0.0), 256);
+        for &dithering in &[
+            Dithering::FloydSteinberg,
+            Dithering::Atkinson,
+            Dithering::Bayer,
+        ] {
+            let white_pixels = white_pixels(dithering, 1.0);
+            assert!(
+                (64..192).contains(&white_pixels),
+                "{:?}: {}",
+                dithering,
+                white_pixels
+            );
+        }
+    }
+
+    #[test]
+    fn should_skip_transparent_pixels() {
+        let mut color_table = ColorTable::new(vec![[0, 0, 0], [255, 255, 255]]);
+        let pixels = [Some([120, 120, 120]), None, Some([255, 255, 255])];
+        assert_eq!(
+            map(&pixels, 3, &mut color_table, Dithering::FloydSteinberg, 1.0),
+            vec![Some(1), None, Some(1)]
+        );
+    }
+}
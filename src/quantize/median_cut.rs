@@ -0,0 +1,98 @@
+/// A box of the RGB color space, holding distinct colors with their number of pixels.
+struct ColorBox {
+    colors: Vec<([u8; 3], u32)>,
+}
+
+impl ColorBox {
+    fn population(&self) -> u64 {
+        self.colors.iter().map(|&(_, count)| u64::from(count)).sum()
+    }
+
+    /// The channel along which the colors spread the most, and their range on it.
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let values = self.colors.iter().map(|(color, _)| color[channel]);
+                let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                (channel, range)
+            })
+            .max_by_key(|&(channel, range)| (range, 3 - channel))
+            .unwrap_or((0, 0))
+    }
+
+    /// A rough measure of the error of representing the whole box by its average.
+    fn priority(&self) -> u64 {
+        u64::from(self.widest_channel().1) * self.population()
+    }
+
+    fn average(&self) -> [u8; 3] {
+        let population = self.population().max(1);
+        let mut average = [0; 3];
+        for (channel, value) in average.iter_mut().enumerate() {
+            let total: u64 = self
+                .colors
+                .iter()
+                .map(|&(color, count)| u64::from(color[channel]) * u64::from(count))
+                .sum();
+            *value = ((total + population / 2) / population) as u8;
+        }
+        average
+    }
+
+    /// Splits the box along its widest channel, leaving half of the pixels on each side.
+    fn split(mut self) -> (ColorBox, ColorBox) {
+        let (channel, _) = self.widest_channel();
+        self.colors.sort_by_key(|&(color, _)| color[channel]);
+
+        let half = self.population() / 2;
+        let mut accumulated = 0;
+        let mut median = self.colors.len() - 1;
+        for (index, &(_, count)) in self.colors.iter().enumerate() {
+            accumulated += u64::from(count);
+            if accumulated >= half {
+                median = index + 1;
+                break;
+            }
+        }
+
+        let median = median.clamp(1, self.colors.len() - 1);
+        let upper = self.colors.split_off(median);
+        (self, ColorBox { colors: upper })
+    }
+}
+
+/// Heckbert's median cut: starting with a box holding every color, the box with the highest
+/// priority is split until there are enough boxes, each contributing its average color.
+pub(super) fn palette(histogram: Vec<([u8; 3], u32)>, max_colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![ColorBox { colors: histogram }];
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, color_box)| color_box.colors.len() > 1)
+            .max_by_key(|(_, color_box)| color_box.priority())
+            .map(|(index, _)| index);
+        match widest {
+            Some(index) => {
+                let (lower, upper) = boxes.swap_remove(index).split();
+                boxes.push(lower);
+                boxes.push(upper);
+            }
+            None => break,
+        }
+    }
+    boxes.iter().map(ColorBox::average).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_split_at_weighted_median() {
+        let histogram = vec![([0, 0, 0], 1), ([10, 0, 0], 1), ([200, 0, 0], 2)];
+        let mut colors = palette(histogram, 2);
+        colors.sort_unstable();
+        assert_eq!(colors, vec![[5, 0, 0], [200, 0, 0]]);
+    }
+}
@@ -0,0 +1,235 @@
+mod color_table;
+mod dither;
+mod median_cut;
+mod neuquant;
+
+pub(crate) use self::color_table::ColorTable;
+pub use self::dither::Dithering;
+use std::collections::HashMap;
+
+/// Pixels with a lower alpha are considered transparent.
+const ALPHA_THRESHOLD: u8 = 128;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QuantizationMethod {
+    // Repeatedly splits the box of the color space with the largest error at its median
+    MedianCut,
+    // Trains Anthony Dekker's NeuQuant network on every `sample_factor`th pixel, from 1 (slowest
+    // and most accurate) to 30 (fastest)
+    NeuQuant { sample_factor: u8 },
+}
+
+/// Reduces truecolor RGBA images to a color table and color indices.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quantizer {
+    pub method: QuantizationMethod,
+    // The size of the color table, between 2 and 256, including the transparent color
+    pub max_colors: usize,
+    // Whether pixels with an alpha below 128 are mapped to a reserved transparent color, rather
+    // than being treated as opaque
+    pub transparency: bool,
+    pub dithering: Dithering,
+    // From 0 (no dithering) to 1 (the usual amount of dithering)
+    pub dithering_strength: f32,
+}
+
+/// The result of quantizing an image, ready to be used as an `IndexedFrame`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuantizedImage {
+    // RGB triplets, the transparent color being the last one
+    pub color_table: Vec<u8>,
+    pub indices: Vec<u8>,
+    // Only set when the image has transparent pixels
+    pub transparent_color_index: Option<u8>,
+}
+
+fn rgb(pixel: &[u8]) -> [u8; 3] {
+    [pixel[0], pixel[1], pixel[2]]
+}
+
+impl Quantizer {
+    /// A quantizer producing up to 256 colors with Floyd-Steinberg dithering, and reserving a
+    /// transparent color when needed.
+    pub fn new(method: QuantizationMethod) -> Quantizer {
+        Quantizer {
+            method,
+            max_colors: 256,
+            transparency: true,
+            dithering: Dithering::FloydSteinberg,
+            dithering_strength: 1.0,
+        }
+    }
+
+    /// Chooses at most `max_colors` colors representing the given ones. Images with few enough
+    /// distinct colors keep them all, so quantizing them is lossless.
+    fn palette(&self, colors: &[[u8; 3]], max_colors: usize) -> Vec<[u8; 3]> {
+        let mut histogram = HashMap::new();
+        for color in colors {
+            *histogram.entry(*color).or_insert(0u32) += 1;
+        }
+        let mut histogram: Vec<_> = histogram.into_iter().collect();
+        histogram.sort_unstable();
+
+        if histogram.len() <= max_colors {
+            return histogram.into_iter().map(|(color, _)| color).collect();
+        }
+        match self.method {
+            QuantizationMethod::MedianCut => median_cut::palette(histogram, max_colors),
+            QuantizationMethod::NeuQuant { sample_factor } => {
+                neuquant::palette(colors, max_colors, sample_factor)
+            }
+        }
+    }
+
+    /// The colors of RGBA pixels, `None` standing for transparent ones.
+    pub(crate) fn colors(&self, pixels: &[u8]) -> Vec<Option<[u8; 3]>> {
+        pixels
+            .chunks_exact(4)
+            .map(|pixel| {
+                if self.transparency && pixel[3] < ALPHA_THRESHOLD {
+                    None
+                } else {
+                    Some(rgb(pixel))
+                }
+            })
+            .collect()
+    }
+
+    /// Builds a color table for the given colors, leaving room for a transparent color if any is
+    /// missing.
+    pub(crate) fn color_table(&self, colors: &[Option<[u8; 3]>]) -> ColorTable {
+        let has_transparency = colors.iter().any(Option::is_none);
+        let opaque_colors: Vec<_> = colors.iter().flatten().cloned().collect();
+        let max_colors = self.max_colors.clamp(2, 256) - has_transparency as usize;
+        let mut palette = self.palette(&opaque_colors, max_colors);
+        if palette.is_empty() {
+            palette.push([0, 0, 0]);
+        }
+        ColorTable::new(palette)
+    }
+
+    /// Maps colors in rows of the given width to a color table after dithering. Transparent
+    /// pixels get the index following the colors of the table, and that color is only added to
+    /// the resulting table with `transparent_color`.
+    pub(crate) fn map(
+        &self,
+        width: u16,
+        colors: &[Option<[u8; 3]>],
+        color_table: &mut ColorTable,
+        transparent_color: bool,
+    ) -> QuantizedImage {
+        let transparent_color_index = color_table.colors().len() as u8;
+        let indices = dither::map(
+            colors,
+            usize::from(width).max(1),
+            color_table,
+            self.dithering,
+            self.dithering_strength,
+        )
+        .into_iter()
+        .map(|index| index.unwrap_or(transparent_color_index))
+        .collect();
+
+        let mut palette = color_table.colors().to_vec();
+        if transparent_color {
+            palette.push([0, 0, 0]);
+        }
+        QuantizedImage {
+            color_table: palette
+                .iter()
+                .flat_map(|color| color.iter().cloned())
+                .collect(),
+            indices,
+            transparent_color_index: if colors.iter().any(Option::is_none) {
+                Some(transparent_color_index)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Quantizes RGBA pixels in rows of the given width, mapping each one to the perceptually
+    /// closest color of the resulting table after dithering.
+    pub fn quantize(&self, width: u16, pixels: &[u8]) -> QuantizedImage {
+        let colors = self.colors(pixels);
+        let mut color_table = self.color_table(&colors);
+        let has_transparency = colors.iter().any(Option::is_none);
+        self.map(width, &colors, &mut color_table, has_transparency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A horizontal red gradient over a vertical blue one.
+    fn gradient() -> Vec<u8> {
+        let mut pixels = Vec::new();
+        for y in 0..64u8 {
+            for x in 0..64u8 {
+                pixels.extend_from_slice(&[x * 4, 128, y * 4, 255]);
+            }
+        }
+        pixels
+    }
+
+    fn mean_squared_error(pixels: &[u8], quantized: &QuantizedImage) -> f64 {
+        let total: u32 = pixels
+            .chunks_exact(4)
+            .zip(&quantized.indices)
+            .map(|(pixel, &index)| {
+                let color = &quantized.color_table[3 * index as usize..][..3];
+                (0..3)
+                    .map(|channel| (i32::from(pixel[channel]) - i32::from(color[channel])).pow(2))
+                    .sum::<i32>() as u32
+            })
+            .sum();
+        f64::from(total) / (pixels.len() / 4) as f64
+    }
+
+    #[test]
+    fn should_keep_few_colors_exactly() {
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255, 9, 9, 9, 0];
+        for method in &[
+            QuantizationMethod::MedianCut,
+            QuantizationMethod::NeuQuant { sample_factor: 10 },
+        ] {
+            assert_eq!(
+                Quantizer::new(*method).quantize(2, &pixels),
+                QuantizedImage {
+                    color_table: vec![0, 0, 255, 255, 0, 0, 0, 0, 0],
+                    indices: vec![1, 0, 1, 2],
+                    transparent_color_index: Some(2),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn should_treat_transparent_pixels_as_opaque_without_transparency() {
+        let mut quantizer = Quantizer::new(QuantizationMethod::MedianCut);
+        quantizer.transparency = false;
+        let quantized = quantizer.quantize(1, &[9, 9, 9, 0]);
+        assert_eq!(quantized.color_table, vec![9, 9, 9]);
+        assert_eq!(quantized.transparent_color_index, None);
+    }
+
+    #[test]
+    fn should_reduce_colors() {
+        let pixels = gradient();
+        for method in &[
+            QuantizationMethod::MedianCut,
+            QuantizationMethod::NeuQuant { sample_factor: 1 },
+            QuantizationMethod::NeuQuant { sample_factor: 10 },
+        ] {
+            let mut quantizer = Quantizer::new(*method);
+            quantizer.max_colors = 16;
+            quantizer.dithering = Dithering::None;
+            let quantized = quantizer.quantize(64, &pixels);
+            assert_eq!(quantized.color_table.len(), 3 * 16);
+            assert_eq!(quantized.indices.len(), 64 * 64);
+            // the best 16 colors split the gradient in 4x4 tiles, with an error of 680
+            assert!(mean_squared_error(&pixels, &quantized) < 2.0 * 680.0);
+        }
+    }
+}
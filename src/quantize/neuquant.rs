@@ -0,0 +1,162 @@
+//! Anthony Dekker's NeuQuant, a Kohonen self-organizing map whose neurons converge to the colors
+//! of an image. See "Kohonen neural networks for optimal colour quantization" (1994).
+//!
+//! This follows the reference implementation, with floating point instead of fixed point
+//! arithmetic.
+
+// Number of times alpha and the radius decrease while learning
+const CYCLES: usize = 100;
+// Steps through the pixels, one of which does not divide the number of pixels
+const PRIMES: [usize; 4] = [499, 491, 487, 503];
+// Images smaller than that are learned in full
+const MIN_SAMPLED_PIXELS: usize = PRIMES[3];
+// Frequency increase of the winning neuron
+const BETA: f64 = 1.0 / 1024.0;
+const GAMMA: f64 = 1024.0;
+const RADIUS_DECREASE: f64 = 30.0;
+
+struct Network {
+    neurons: Vec<[f64; 3]>,
+    frequencies: Vec<f64>,
+    biases: Vec<f64>,
+}
+
+impl Network {
+    /// Neurons start out evenly spread along the gray diagonal.
+    fn new(size: usize) -> Network {
+        Network {
+            neurons: (0..size)
+                .map(|index| [(index * 256 / size) as f64; 3])
+                .collect(),
+            frequencies: vec![1.0 / size as f64; size],
+            biases: vec![0.0; size],
+        }
+    }
+
+    /// Finds the neuron closest to a color, biased against the neurons that won too often so that
+    /// every neuron ends up representing a similar number of pixels.
+    fn contest(&mut self, color: [f64; 3]) -> usize {
+        let mut best = (f64::MAX, 0);
+        let mut best_biased = (f64::MAX, 0);
+        for (index, neuron) in self.neurons.iter().enumerate() {
+            let distance: f64 = (0..3)
+                .map(|channel| (neuron[channel] - color[channel]).abs())
+                .sum();
+            if distance < best.0 {
+                best = (distance, index);
+            }
+            let biased_distance = distance - self.biases[index];
+            if biased_distance < best_biased.0 {
+                best_biased = (biased_distance, index);
+            }
+
+            let beta_frequency = BETA * self.frequencies[index];
+            self.frequencies[index] -= beta_frequency;
+            self.biases[index] += beta_frequency * GAMMA;
+        }
+        self.frequencies[best.1] += BETA;
+        self.biases[best.1] -= BETA * GAMMA;
+        best_biased.1
+    }
+
+    /// Moves a neuron towards a color by a fraction of their difference.
+    fn alter(&mut self, index: usize, fraction: f64, color: [f64; 3]) {
+        let neuron = &mut self.neurons[index];
+        for channel in 0..3 {
+            neuron[channel] -= fraction * (neuron[channel] - color[channel]);
+        }
+    }
+
+    /// Moves the neighbours of a neuron less the further they are, up to the given radius.
+    fn alter_neighbours(&mut self, index: usize, radius: usize, alpha: f64, color: [f64; 3]) {
+        let radius_squared = (radius * radius) as f64;
+        for distance in 1..radius {
+            let fraction = alpha * (radius_squared - (distance * distance) as f64) / radius_squared;
+            if index + distance < self.neurons.len() {
+                self.alter(index + distance, fraction, color);
+            }
+            if index >= distance {
+                self.alter(index - distance, fraction, color);
+            }
+        }
+    }
+}
+
+fn effective_radius(radius: f64) -> usize {
+    match radius as usize {
+        0 | 1 => 0,
+        radius => radius,
+    }
+}
+
+/// Learns `size` colors representing the given pixels.
+pub(super) fn palette(pixels: &[[u8; 3]], size: usize, sample_factor: u8) -> Vec<[u8; 3]> {
+    let sample_factor = if pixels.len() < MIN_SAMPLED_PIXELS {
+        1
+    } else {
+        usize::from(sample_factor.clamp(1, 30))
+    };
+    let alpha_decrease = (30 + (sample_factor - 1) / 3) as f64;
+    let samples = (pixels.len() / sample_factor).max(1);
+    let delta = (samples / CYCLES).max(1);
+    let step = PRIMES
+        .iter()
+        .cloned()
+        .find(|&prime| !pixels.len().is_multiple_of(prime))
+        .unwrap_or(PRIMES[3]);
+
+    let mut network = Network::new(size);
+    let mut alpha = 1.0;
+    let mut radius = (size / 8) as f64;
+    let mut position = 0;
+    for sample in 1..=samples {
+        let pixel = pixels[position];
+        let color = [
+            f64::from(pixel[0]),
+            f64::from(pixel[1]),
+            f64::from(pixel[2]),
+        ];
+
+        let winner = network.contest(color);
+        network.alter(winner, alpha, color);
+        let effective_radius = effective_radius(radius);
+        if effective_radius > 0 {
+            network.alter_neighbours(winner, effective_radius, alpha, color);
+        }
+
+        position = (position + step) % pixels.len();
+        if sample % delta == 0 {
+            alpha -= alpha / alpha_decrease;
+            radius -= radius / RADIUS_DECREASE;
+        }
+    }
+
+    network
+        .neurons
+        .iter()
+        .map(|neuron| {
+            let mut color = [0; 3];
+            for (value, channel) in color.iter_mut().zip(neuron) {
+                *value = channel.round().clamp(0.0, 255.0) as u8;
+            }
+            color
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_converge_to_distinct_colors() {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let pixels: Vec<_> = colors.iter().cycle().take(4000).cloned().collect();
+        let mut palette = palette(&pixels, 4, 1);
+        palette.sort_unstable();
+        assert_eq!(
+            palette,
+            vec![[0, 0, 255], [0, 255, 0], [255, 0, 0], [255, 255, 255]]
+        );
+    }
+}
@@ -0,0 +1,517 @@
+use super::lzw::{decode, DecodingError};
+use super::model::{
+    Block, DisposalMethod, GraphicControlExtension, ImageData, ImageDescriptor, GIF,
+};
+
+/// An image made of RGBA pixels in row order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Creates a fully transparent image.
+    pub fn new(width: u16, height: u16) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn pixel(&self, x: u16, y: u16) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// The pixels of a rectangle, which must lie within the image.
+    pub fn crop(&self, left: u16, top: u16, width: u16, height: u16) -> RgbaImage {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in top as usize..top as usize + height as usize {
+            let start = (y * self.width as usize + left as usize) * 4;
+            pixels.extend_from_slice(&self.pixels[start..start + width as usize * 4]);
+        }
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Copies the opaque pixels of `image` onto this image at the given position, clipping
+    /// whatever falls outside.
+    pub(crate) fn draw(&mut self, image: &RgbaImage, left: u16, top: u16) {
+        for y in 0..image.height {
+            let canvas_y = top as usize + y as usize;
+            if canvas_y >= self.height as usize {
+                break;
+            }
+            for x in 0..image.width {
+                let canvas_x = left as usize + x as usize;
+                if canvas_x >= self.width as usize {
+                    break;
+                }
+                let source = (y as usize * image.width as usize + x as usize) * 4;
+                if image.pixels[source + 3] == 0 {
+                    continue;
+                }
+                let target = (canvas_y * self.width as usize + canvas_x) * 4;
+                self.pixels[target..target + 4].copy_from_slice(&image.pixels[source..source + 4]);
+            }
+        }
+    }
+
+    /// Makes a rectangle transparent, clipping whatever falls outside.
+    pub(crate) fn clear(&mut self, image_descriptor: &ImageDescriptor) {
+        let right = (image_descriptor.left as usize + image_descriptor.width as usize)
+            .min(self.width as usize);
+        let bottom = (image_descriptor.top as usize + image_descriptor.height as usize)
+            .min(self.height as usize);
+        for y in image_descriptor.top as usize..bottom {
+            for x in image_descriptor.left as usize..right {
+                let i = (y * self.width as usize + x) * 4;
+                self.pixels[i..i + 4].copy_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+    }
+}
+
+/// The rows of an interlaced image in the order in which they are stored.
+///
+/// See the GIF89a spec Appendix E
+pub(crate) fn interlaced_rows(height: usize) -> impl Iterator<Item = usize> {
+    (0..height)
+        .step_by(8)
+        .chain((4..height).step_by(8))
+        .chain((2..height).step_by(4))
+        .chain((1..height).step_by(2))
+}
+
+/// Decodes the color indices of an image in row order, undoing interlacing. Pixels missing from
+/// truncated image data are set to 0.
+pub fn decode_indices(
+    image_descriptor: &ImageDescriptor,
+    image_data: &ImageData<'_>,
+) -> Result<Vec<u8>, DecodingError> {
+    let width = image_descriptor.width as usize;
+    let height = image_descriptor.height as usize;
+
+    let mut indices = decode(
+        image_data.lzw_minimum_code_size,
+        image_data.data,
+        width * height,
+    )?;
+    indices.resize(width * height, 0);
+
+    if !image_descriptor.interlace_flag() {
+        return Ok(indices);
+    }
+
+    let mut deinterlaced = vec![0; width * height];
+    for (row, y) in interlaced_rows(height).enumerate() {
+        deinterlaced[y * width..(y + 1) * width]
+            .copy_from_slice(&indices[row * width..(row + 1) * width]);
+    }
+    Ok(deinterlaced)
+}
+
+/// Looks up color indices in a color table. The transparent color and indices outside of the
+/// table become fully transparent pixels.
+pub fn indices_to_rgba(
+    indices: &[u8],
+    color_table: &[u8],
+    transparent_color: Option<u8>,
+) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(indices.len() * 4);
+    for &index in indices {
+        let i = index as usize * 3;
+        if Some(index) == transparent_color || i + 3 > color_table.len() {
+            pixels.extend_from_slice(&[0, 0, 0, 0]);
+        } else {
+            pixels.extend_from_slice(&color_table[i..i + 3]);
+            pixels.push(0xff);
+        }
+    }
+    pixels
+}
+
+/// Decodes a Table-Based Image into an RGBA image the size of its Image Descriptor, using the
+/// Local Color Table if there is one and the Global Color Table otherwise.
+pub fn decode_image(
+    graphic_control_extension: Option<&GraphicControlExtension>,
+    image_descriptor: &ImageDescriptor,
+    color_table: Option<&[u8]>,
+    image_data: &ImageData<'_>,
+) -> Result<RgbaImage, DecodingError> {
+    let indices = decode_indices(image_descriptor, image_data)?;
+    let transparent_color = graphic_control_extension
+        .filter(|graphic_control_extension| graphic_control_extension.transparent_color_flag())
+        .map(|graphic_control_extension| graphic_control_extension.transparent_color_index);
+
+    Ok(RgbaImage {
+        width: image_descriptor.width,
+        height: image_descriptor.height,
+        pixels: indices_to_rgba(&indices, color_table.unwrap_or(&[]), transparent_color),
+    })
+}
+
+/// A fully composited frame of an animation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderedFrame {
+    // Index of the frame's graphic block in `GIF::data`
+    pub block: usize,
+    pub delay_time: u16,
+    pub image: RgbaImage,
+}
+
+/// What needs to be done to the canvas before the next frame is drawn.
+struct Disposal {
+    disposal_method: DisposalMethod,
+    image_descriptor: ImageDescriptor,
+    previous_canvas: Option<RgbaImage>,
+}
+
+/// The state of compositing the frames of a GIF, kept apart from the GIF itself so that it can be
+/// stored next to an owned `GIF`.
+pub(crate) struct Compositor {
+    next_block: usize,
+    // The last Graphic Control Extension since the previous graphic rendering block
+    pending_graphic_control_extension: Option<GraphicControlExtension>,
+    canvas: RgbaImage,
+    disposal: Option<Disposal>,
+}
+
+impl Compositor {
+    pub(crate) fn new(gif: &GIF<'_>) -> Compositor {
+        Compositor {
+            next_block: 0,
+            pending_graphic_control_extension: None,
+            canvas: RgbaImage::new(gif.width, gif.height),
+            disposal: None,
+        }
+    }
+
+    fn dispose(&mut self) {
+        match self.disposal.take() {
+            Some(Disposal {
+                disposal_method: DisposalMethod::RestoreToBackgroundColor,
+                image_descriptor,
+                ..
+            }) => self.canvas.clear(&image_descriptor),
+            Some(Disposal {
+                disposal_method: DisposalMethod::RestoreToPrevious,
+                previous_canvas: Some(previous_canvas),
+                ..
+            }) => self.canvas = previous_canvas,
+            _ => {}
+        }
+    }
+
+    /// The canvas once the last composited frame is disposed of, on which the next one is drawn.
+    pub(crate) fn disposed_canvas(&self) -> RgbaImage {
+        match &self.disposal {
+            Some(Disposal {
+                disposal_method: DisposalMethod::RestoreToBackgroundColor,
+                image_descriptor,
+                ..
+            }) => {
+                let mut canvas = self.canvas.clone();
+                canvas.clear(image_descriptor);
+                canvas
+            }
+            Some(Disposal {
+                disposal_method: DisposalMethod::RestoreToPrevious,
+                previous_canvas: Some(previous_canvas),
+                ..
+            }) => previous_canvas.clone(),
+            _ => self.canvas.clone(),
+        }
+    }
+
+    /// Composites the next image of `gif`, which must be the GIF this compositor was created for.
+    pub(crate) fn next_frame(
+        &mut self,
+        gif: &GIF<'_>,
+    ) -> Option<Result<RenderedFrame, DecodingError>> {
+        while let Some(block) = gif.data.get(self.next_block) {
+            let index = self.next_block;
+            self.next_block += 1;
+
+            match block {
+                Block::GraphicControlExtension(graphic_control_extension) => {
+                    self.pending_graphic_control_extension = Some(*graphic_control_extension);
+                    continue;
+                }
+                Block::TextBlock { .. } => self.pending_graphic_control_extension = None,
+                _ => {}
+            }
+
+            if let Block::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } = block
+            {
+                // like browsers, the last Graphic Control Extension before the image applies to it
+                let pending_graphic_control_extension =
+                    self.pending_graphic_control_extension.take();
+                let graphic_control_extension =
+                    graphic_control_extension.or(pending_graphic_control_extension);
+                let image = match decode_image(
+                    graphic_control_extension.as_ref(),
+                    image_descriptor,
+                    local_color_table.or(gif.global_color_table),
+                    image_data,
+                ) {
+                    Ok(image) => image,
+                    Err(err) => return Some(Err(err)),
+                };
+
+                self.dispose();
+
+                let disposal_method = graphic_control_extension
+                    .map_or(DisposalMethod::Unspecified, |graphic_control_extension| {
+                        graphic_control_extension.disposal_method()
+                    });
+                self.disposal = Some(Disposal {
+                    disposal_method,
+                    image_descriptor: *image_descriptor,
+                    previous_canvas: if disposal_method == DisposalMethod::RestoreToPrevious {
+                        Some(self.canvas.clone())
+                    } else {
+                        None
+                    },
+                });
+
+                self.canvas
+                    .draw(&image, image_descriptor.left, image_descriptor.top);
+
+                return Some(Ok(RenderedFrame {
+                    block: index,
+                    delay_time: graphic_control_extension.map_or(0, |graphic_control_extension| {
+                        graphic_control_extension.delay_time
+                    }),
+                    image: self.canvas.clone(),
+                }));
+            }
+        }
+        None
+    }
+}
+
+/// Composites the images of a GIF on a canvas the size of the Logical Screen, following the
+/// disposal method of each frame.
+///
+/// Like browsers, the canvas starts out transparent and "restore to background color" clears to
+/// transparent rather than to the background color. Plain text blocks are not rendered.
+pub struct Renderer<'a, 'b> {
+    gif: &'b GIF<'a>,
+    compositor: Compositor,
+}
+
+impl<'a, 'b> Renderer<'a, 'b> {
+    pub fn new(gif: &'b GIF<'a>) -> Renderer<'a, 'b> {
+        Renderer {
+            gif,
+            compositor: Compositor::new(gif),
+        }
+    }
+}
+
+impl<'a, 'b> Iterator for Renderer<'a, 'b> {
+    type Item = Result<RenderedFrame, DecodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.compositor.next_frame(self.gif)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::model::{GIFVersion, SubBlocks};
+    use super::*;
+
+    // A 2x2 image of a single color index, encoded with an LZW minimum code size of 2
+    fn image_data(index: u8) -> ImageData<'static> {
+        match index {
+            // clear code, 1, 6, 1, end code
+            1 => ImageData {
+                lzw_minimum_code_size: 2,
+                data: SubBlocks(&[0x02, 0x8c, 0x53, 0x00]),
+            },
+            // clear code, 2, 6, 2, end code
+            _ => ImageData {
+                lzw_minimum_code_size: 2,
+                data: SubBlocks(&[0x02, 0x94, 0x55, 0x00]),
+            },
+        }
+    }
+
+    fn graphic_block(disposal_method: u8, left: u16, top: u16, index: u8) -> Block<'static> {
+        Block::GraphicBlock {
+            graphic_control_extension: Some(GraphicControlExtension {
+                byte_size: 4,
+                packed_field: disposal_method << 2,
+                delay_time: 10,
+                transparent_color_index: 0,
+            }),
+            image_descriptor: ImageDescriptor {
+                left,
+                top,
+                width: 2,
+                height: 2,
+                packed_field: 0,
+            },
+            local_color_table: None,
+            image_data: image_data(index),
+        }
+    }
+
+    fn gif(data: Vec<Block<'static>>) -> GIF<'static> {
+        GIF {
+            version: GIFVersion::GIF89a,
+            width: 3,
+            height: 2,
+            packed_field: 0x81,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: Some(&[0, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 255][..]),
+            data,
+            trailer: true,
+        }
+    }
+
+    const NONE: [u8; 4] = [0, 0, 0, 0];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn pixels(image: &RgbaImage) -> Vec<[u8; 4]> {
+        (0..image.height)
+            .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+            .map(|(x, y)| image.pixel(x, y))
+            .collect()
+    }
+
+    fn render(gif: &GIF<'_>) -> Vec<Vec<[u8; 4]>> {
+        Renderer::new(gif)
+            .map(|frame| pixels(&frame.unwrap().image))
+            .collect()
+    }
+
+    #[test]
+    fn should_deinterlace_rows() {
+        let rows: Vec<usize> = interlaced_rows(10).collect();
+        assert_eq!(rows, vec![0, 8, 4, 2, 6, 1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn should_crop() {
+        let mut image = RgbaImage::new(3, 2);
+        image.pixels[4 * 4..5 * 4].copy_from_slice(&[1, 2, 3, 4]);
+        let cropped = image.crop(1, 1, 2, 1);
+        assert_eq!(cropped.pixels, vec![1, 2, 3, 4, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn should_not_dispose() {
+        let gif = gif(vec![graphic_block(1, 0, 0, 1), graphic_block(1, 1, 0, 2)]);
+        assert_eq!(
+            render(&gif),
+            vec![
+                vec![RED, RED, NONE, RED, RED, NONE],
+                vec![RED, BLUE, BLUE, RED, BLUE, BLUE],
+            ]
+        );
+    }
+
+    #[test]
+    fn should_restore_to_background_color() {
+        let gif = gif(vec![graphic_block(2, 0, 0, 1), graphic_block(1, 1, 0, 2)]);
+        assert_eq!(
+            render(&gif),
+            vec![
+                vec![RED, RED, NONE, RED, RED, NONE],
+                vec![NONE, BLUE, BLUE, NONE, BLUE, BLUE],
+            ]
+        );
+    }
+
+    #[test]
+    fn should_restore_to_previous() {
+        let gif = gif(vec![
+            graphic_block(1, 0, 0, 1),
+            graphic_block(3, 1, 0, 2),
+            graphic_block(1, 2, 1, 2),
+        ]);
+        let frames = render(&gif);
+        assert_eq!(frames[1], vec![RED, BLUE, BLUE, RED, BLUE, BLUE]);
+        assert_eq!(frames[2], vec![RED, RED, NONE, RED, RED, BLUE]);
+    }
+
+    #[test]
+    fn should_skip_transparent_pixels() {
+        let mut second = graphic_block(1, 1, 0, 2);
+        if let Block::GraphicBlock {
+            graphic_control_extension: Some(ref mut graphic_control_extension),
+            ..
+        } = second
+        {
+            graphic_control_extension.packed_field |= 0x01;
+            graphic_control_extension.transparent_color_index = 2;
+        }
+        let gif = gif(vec![graphic_block(1, 0, 0, 1), second]);
+        assert_eq!(render(&gif)[1], vec![RED, RED, NONE, RED, RED, NONE]);
+    }
+
+    #[test]
+    fn should_apply_last_stray_graphic_control_extension() {
+        let detached = |mut block: Block<'static>| {
+            if let Block::GraphicBlock {
+                graphic_control_extension,
+                ..
+            } = &mut block
+            {
+                *graphic_control_extension = None;
+            }
+            block
+        };
+        let transparency = GraphicControlExtension::new(DisposalMethod::DoNotDispose, 5, Some(2));
+
+        let detached_gif = gif(vec![
+            graphic_block(1, 0, 0, 1),
+            Block::GraphicControlExtension(GraphicControlExtension::new(
+                DisposalMethod::RestoreToPrevious,
+                20,
+                None,
+            )),
+            Block::CommentExtension(SubBlocks(&[0x00])),
+            Block::GraphicControlExtension(transparency),
+            detached(graphic_block(1, 1, 0, 2)),
+        ]);
+        let frames: Vec<_> = Renderer::new(&detached_gif).map(Result::unwrap).collect();
+        assert_eq!(frames[1].delay_time, 5);
+        assert_eq!(
+            pixels(&frames[1].image),
+            vec![RED, RED, NONE, RED, RED, NONE]
+        );
+
+        // a Plain Text Extension takes the stray extension
+        let text = gif(vec![
+            Block::GraphicControlExtension(transparency),
+            Block::TextBlock {
+                graphic_control_extension: None,
+                text: SubBlocks(&[0x00]),
+            },
+            detached(graphic_block(1, 1, 0, 2)),
+        ]);
+        assert_eq!(render(&text)[0], vec![NONE, BLUE, BLUE, NONE, BLUE, BLUE]);
+    }
+}
@@ -0,0 +1,145 @@
+use super::model::GIF;
+use super::render::RenderedFrame;
+use std::time::Duration;
+
+/// How the `delay_time` of frames, in hundredths of a second, translates into display time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DelayPolicy {
+    // Delays as written in the Graphic Control Extensions, frames without one having no delay
+    Raw,
+    // Delays as shown by browsers, which treat 0 and 1 as 10 so that "as fast as possible"
+    // animations do not hog the CPU
+    Browser,
+}
+
+impl DelayPolicy {
+    /// The delay a frame is displayed for, in hundredths of a second.
+    pub fn normalize(self, delay_time: u16) -> u16 {
+        match self {
+            DelayPolicy::Browser if delay_time <= 1 => 10,
+            _ => delay_time,
+        }
+    }
+
+    /// The time a frame is displayed for.
+    pub fn duration(self, delay_time: u16) -> Duration {
+        Duration::from_millis(u64::from(self.normalize(delay_time)) * 10)
+    }
+}
+
+impl<'a> GIF<'a> {
+    /// The time each image is displayed for, in order.
+    pub fn frame_durations(&self, policy: DelayPolicy) -> Vec<Duration> {
+        self.frames()
+            .map(|frame| policy.duration(frame.delay_time()))
+            .collect()
+    }
+
+    /// The time at which each image is first displayed, starting at 0.
+    pub fn timestamps(&self, policy: DelayPolicy) -> Vec<Duration> {
+        self.frame_durations(policy)
+            .iter()
+            .scan(Duration::from_secs(0), |timestamp, duration| {
+                let start = *timestamp;
+                *timestamp += *duration;
+                Some(start)
+            })
+            .collect()
+    }
+
+    /// The time a single play of the animation takes, regardless of the loop count.
+    pub fn duration(&self, policy: DelayPolicy) -> Duration {
+        self.frame_durations(policy).iter().sum()
+    }
+}
+
+impl RenderedFrame {
+    /// The time this frame is displayed for.
+    pub fn duration(&self, policy: DelayPolicy) -> Duration {
+        policy.duration(self.delay_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::model::{
+        Block, DisposalMethod, GIFVersion, GraphicControlExtension, ImageData, ImageDescriptor,
+        SubBlocks,
+    };
+    use super::*;
+
+    fn image(delay_time: Option<u16>) -> Block<'static> {
+        Block::GraphicBlock {
+            graphic_control_extension: delay_time.map(|delay_time| {
+                GraphicControlExtension::new(DisposalMethod::Unspecified, delay_time, None)
+            }),
+            image_descriptor: ImageDescriptor {
+                left: 0,
+                top: 0,
+                width: 1,
+                height: 1,
+                packed_field: 0,
+            },
+            local_color_table: None,
+            image_data: ImageData {
+                lzw_minimum_code_size: 2,
+                data: SubBlocks(&[0x02, 0x44, 0x01, 0x00]),
+            },
+        }
+    }
+
+    fn gif(data: Vec<Block<'static>>) -> GIF<'static> {
+        GIF {
+            version: GIFVersion::GIF89a,
+            width: 1,
+            height: 1,
+            packed_field: 0,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: None,
+            data,
+            trailer: true,
+        }
+    }
+
+    #[test]
+    fn should_normalize_delays_like_browsers() {
+        assert_eq!(DelayPolicy::Raw.normalize(0), 0);
+        assert_eq!(DelayPolicy::Browser.normalize(0), 10);
+        assert_eq!(DelayPolicy::Browser.normalize(1), 10);
+        assert_eq!(DelayPolicy::Browser.normalize(2), 2);
+        assert_eq!(DelayPolicy::Browser.duration(1), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn should_time_frames() {
+        let gif = gif(vec![
+            image(Some(5)),
+            Block::CommentExtension(SubBlocks(&[0x00])),
+            image(Some(0)),
+            image(None),
+            image(Some(20)),
+        ]);
+        let millis = |durations: Vec<Duration>| -> Vec<u128> {
+            durations.iter().map(Duration::as_millis).collect()
+        };
+
+        assert_eq!(
+            millis(gif.frame_durations(DelayPolicy::Raw)),
+            vec![50, 0, 0, 200]
+        );
+        assert_eq!(
+            millis(gif.frame_durations(DelayPolicy::Browser)),
+            vec![50, 100, 100, 200]
+        );
+        assert_eq!(
+            millis(gif.timestamps(DelayPolicy::Browser)),
+            vec![0, 50, 150, 250]
+        );
+        assert_eq!(gif.duration(DelayPolicy::Raw), Duration::from_millis(250));
+        assert_eq!(
+            gif.duration(DelayPolicy::Browser),
+            Duration::from_millis(450)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use super::model::{Block, GIFVersion, GIF};
+
+/// The kinds of extension blocks that can be stripped from a GIF.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Extension {
+    GraphicControl,
+    PlainText,
+    Application,
+    Comment,
+    Unknown(u8),
+}
+
+/// An extension that was removed from a GIF, with the index of the block it belonged to in the
+/// original `GIF::data`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StrippedExtension {
+    pub block: usize,
+    pub extension: Extension,
+}
+
+/// Strips all extensions from a GIF so it can be read by consumers that only accept "87a".
+///
+/// Graphic Control Extensions are removed from their images, losing delays, disposal methods and
+/// transparency, while Plain Text, Application, Comment and unknown extension blocks are dropped
+/// altogether. Everything that was removed is reported.
+pub fn downgrade_to_gif87a<'a>(gif: &GIF<'a>) -> (GIF<'a>, Vec<StrippedExtension>) {
+    let mut stripped = Vec::new();
+    let mut data = Vec::with_capacity(gif.data.len());
+
+    for (index, block) in gif.data.iter().enumerate() {
+        let mut strip = |extension| {
+            stripped.push(StrippedExtension {
+                block: index,
+                extension,
+            })
+        };
+
+        match *block {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } => {
+                if graphic_control_extension.is_some() {
+                    strip(Extension::GraphicControl);
+                }
+                data.push(Block::GraphicBlock {
+                    graphic_control_extension: None,
+                    image_descriptor,
+                    local_color_table,
+                    image_data,
+                });
+            }
+            Block::TextBlock {
+                graphic_control_extension,
+                ..
+            } => {
+                if graphic_control_extension.is_some() {
+                    strip(Extension::GraphicControl);
+                }
+                strip(Extension::PlainText);
+            }
+            Block::ApplicationExtension(_) => strip(Extension::Application),
+            Block::CommentExtension(_) => strip(Extension::Comment),
+            Block::GraphicControlExtension(_) => strip(Extension::GraphicControl),
+            Block::UnknownExtension { label, .. } => strip(Extension::Unknown(label)),
+        }
+    }
+
+    let downgraded = GIF {
+        version: GIFVersion::GIF87a,
+        width: gif.width,
+        height: gif.height,
+        packed_field: gif.packed_field,
+        background_color_index: gif.background_color_index,
+        pixel_aspect_ratio: gif.pixel_aspect_ratio,
+        global_color_table: gif.global_color_table,
+        data,
+        trailer: gif.trailer,
+    };
+
+    (downgraded, stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::model::{GraphicControlExtension, ImageData, ImageDescriptor, SubBlocks};
+    use super::*;
+
+    #[test]
+    fn should_strip_all_extensions() {
+        let image_descriptor = ImageDescriptor {
+            left: 0,
+            top: 0,
+            width: 1,
+            height: 1,
+            packed_field: 0,
+        };
+        let image_data = ImageData {
+            lzw_minimum_code_size: 2,
+            data: SubBlocks(&[0x02, 0x44, 0x01, 0x00][..]),
+        };
+        let gif = GIF {
+            version: GIFVersion::GIF89a,
+            width: 1,
+            height: 1,
+            packed_field: 0x80,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: Some(&[0, 0, 0, 255, 255, 255][..]),
+            data: vec![
+                Block::ApplicationExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+                Block::GraphicBlock {
+                    graphic_control_extension: Some(GraphicControlExtension {
+                        byte_size: 4,
+                        packed_field: 0,
+                        delay_time: 10,
+                        transparent_color_index: 0,
+                    }),
+                    image_descriptor,
+                    local_color_table: None,
+                    image_data,
+                },
+                Block::CommentExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+                Block::UnknownExtension {
+                    label: 0x99,
+                    data: SubBlocks(&[0x00][..]),
+                },
+            ],
+            trailer: true,
+        };
+
+        let (downgraded, stripped) = downgrade_to_gif87a(&gif);
+
+        assert_eq!(downgraded.version, GIFVersion::GIF87a);
+        assert_eq!(downgraded.required_version(), GIFVersion::GIF87a);
+        assert_eq!(
+            downgraded.data,
+            vec![Block::GraphicBlock {
+                graphic_control_extension: None,
+                image_descriptor,
+                local_color_table: None,
+                image_data,
+            }]
+        );
+        assert_eq!(
+            stripped,
+            vec![
+                StrippedExtension {
+                    block: 0,
+                    extension: Extension::Application
+                },
+                StrippedExtension {
+                    block: 1,
+                    extension: Extension::GraphicControl
+                },
+                StrippedExtension {
+                    block: 2,
+                    extension: Extension::Comment
+                },
+                StrippedExtension {
+                    block: 3,
+                    extension: Extension::Unknown(0x99)
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,585 @@
+use super::model::{Block, GIFVersion, GraphicControlExtension, ImageDescriptor, GIF};
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// How serious a spec violation is. Errors are likely to break decoders, while warnings are
+/// usually tolerated by them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Where in the GIF a violation was found.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Location {
+    // Index into `GIF::data`
+    Block(usize),
+    Trailer,
+}
+
+/// A deviation from the GIF89a spec.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Violation {
+    // See the GIF89a spec §23: the block size must be 4
+    InvalidGraphicControlExtensionSize(u8),
+    // Extensions were introduced in 89a
+    ExtensionInGIF87a,
+    // Neither a Local nor a Global Color Table is available for an image
+    MissingColorTable,
+    // The image does not fit in the Logical Screen (§18, §20)
+    ImageOutsideLogicalScreen,
+    // Bits marked as reserved in a packed field are set (§20, §23)
+    ReservedBitsSet,
+    TransparentColorIndexOutOfRange { index: u8, color_table_size: usize },
+    // See the GIF89a spec Appendix F
+    InvalidLZWMinimumCodeSize(u8),
+    // See the GIF89a spec §27
+    MissingTrailer,
+    // A Graphic Control Extension is followed by another one before any graphic rendering block,
+    // which then takes precedence as in browsers (§23)
+    SupersededGraphicControlExtension,
+    // Other extensions separate a Graphic Control Extension from the graphic rendering block it
+    // applies to (§23)
+    DetachedGraphicControlExtension,
+    // A Graphic Control Extension is not followed by any graphic rendering block (§23)
+    UnusedGraphicControlExtension,
+}
+
+impl Violation {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Violation::InvalidGraphicControlExtensionSize(_)
+            | Violation::MissingColorTable
+            | Violation::InvalidLZWMinimumCodeSize(_) => Severity::Error,
+            Violation::ExtensionInGIF87a
+            | Violation::ImageOutsideLogicalScreen
+            | Violation::ReservedBitsSet
+            | Violation::TransparentColorIndexOutOfRange { .. }
+            | Violation::MissingTrailer
+            | Violation::SupersededGraphicControlExtension
+            | Violation::DetachedGraphicControlExtension
+            | Violation::UnusedGraphicControlExtension => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::InvalidGraphicControlExtensionSize(size) => write!(
+                f,
+                "graphic control extension has a block size of {} instead of 4",
+                size
+            ),
+            Violation::ExtensionInGIF87a => write!(f, "extension block in a GIF87a file"),
+            Violation::MissingColorTable => {
+                write!(f, "image has neither a local nor a global color table")
+            }
+            Violation::ImageOutsideLogicalScreen => {
+                write!(f, "image extends outside the logical screen")
+            }
+            Violation::ReservedBitsSet => write!(f, "reserved bits are set"),
+            Violation::TransparentColorIndexOutOfRange {
+                index,
+                color_table_size,
+            } => write!(
+                f,
+                "transparent color index {} is outside the color table of size {}",
+                index, color_table_size
+            ),
+            Violation::InvalidLZWMinimumCodeSize(size) => {
+                write!(f, "LZW minimum code size {} is outside of 2-8", size)
+            }
+            Violation::MissingTrailer => write!(f, "missing trailer"),
+            Violation::SupersededGraphicControlExtension => {
+                write!(f, "graphic control extension is overridden by a later one")
+            }
+            Violation::DetachedGraphicControlExtension => write!(
+                f,
+                "graphic control extension is separated from its graphic rendering block"
+            ),
+            Violation::UnusedGraphicControlExtension => write!(
+                f,
+                "graphic control extension is not followed by a graphic rendering block"
+            ),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub violation: Violation,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.location {
+            Location::Block(index) => write!(f, "{} in block {}: ", severity, index)?,
+            Location::Trailer => write!(f, "{} in trailer: ", severity)?,
+        }
+        write!(f, "{}", self.violation)
+    }
+}
+
+struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    fn report(&mut self, location: Location, violation: Violation) {
+        self.0.push(Diagnostic {
+            severity: violation.severity(),
+            location,
+            violation,
+        });
+    }
+
+    fn check_graphic_control_extension(
+        &mut self,
+        location: Location,
+        graphic_control_extension: &GraphicControlExtension,
+        color_table: Option<&[u8]>,
+    ) {
+        if graphic_control_extension.byte_size != 4 {
+            self.report(
+                location,
+                Violation::InvalidGraphicControlExtensionSize(graphic_control_extension.byte_size),
+            );
+        }
+        if graphic_control_extension.packed_field & 0b_1110_0000 != 0 {
+            self.report(location, Violation::ReservedBitsSet);
+        }
+        if let (true, Some(color_table)) = (
+            graphic_control_extension.transparent_color_flag(),
+            color_table,
+        ) {
+            let index = graphic_control_extension.transparent_color_index;
+            if index as usize >= color_table.len() / 3 {
+                self.report(
+                    location,
+                    Violation::TransparentColorIndexOutOfRange {
+                        index,
+                        color_table_size: color_table.len() / 3,
+                    },
+                );
+            }
+        }
+    }
+
+    fn check_image_descriptor(
+        &mut self,
+        location: Location,
+        image_descriptor: &ImageDescriptor,
+        gif: &GIF<'_>,
+    ) {
+        if image_descriptor.packed_field & 0b_0001_1000 != 0 {
+            self.report(location, Violation::ReservedBitsSet);
+        }
+        if u32::from(image_descriptor.left) + u32::from(image_descriptor.width)
+            > u32::from(gif.width)
+            || u32::from(image_descriptor.top) + u32::from(image_descriptor.height)
+                > u32::from(gif.height)
+        {
+            self.report(location, Violation::ImageOutsideLogicalScreen);
+        }
+    }
+}
+
+/// The color table of the graphic rendering block controlled by the stray Graphic Control
+/// Extension at `index`, resolved like `GIF::frames` does. `None` when another Graphic Control
+/// Extension supersedes it first.
+fn controlled_color_table<'a>(gif: &GIF<'a>, index: usize) -> Option<&'a [u8]> {
+    for block in &gif.data[index + 1..] {
+        match block {
+            Block::GraphicControlExtension(_)
+            | Block::GraphicBlock {
+                graphic_control_extension: Some(_),
+                ..
+            }
+            | Block::TextBlock {
+                graphic_control_extension: Some(_),
+                ..
+            } => return None,
+            Block::GraphicBlock {
+                local_color_table, ..
+            } => return local_color_table.or(gif.global_color_table),
+            Block::TextBlock { .. } => return gif.global_color_table,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Checks a parsed GIF against the GIF89a spec, returning all violations found in the order in
+/// which they appear.
+pub fn validate(gif: &GIF<'_>) -> Vec<Diagnostic> {
+    let mut diagnostics = Diagnostics(Vec::new());
+    // a Graphic Control Extension waiting for the next graphic rendering block
+    let mut pending_graphic_control_extension = None;
+
+    for (index, block) in gif.data.iter().enumerate() {
+        let location = Location::Block(index);
+
+        if let Some(pending) = pending_graphic_control_extension {
+            match block {
+                Block::GraphicControlExtension(_)
+                | Block::GraphicBlock {
+                    graphic_control_extension: Some(_),
+                    ..
+                }
+                | Block::TextBlock {
+                    graphic_control_extension: Some(_),
+                    ..
+                } => diagnostics.report(pending, Violation::SupersededGraphicControlExtension),
+                Block::GraphicBlock { .. } | Block::TextBlock { .. } => {
+                    diagnostics.report(pending, Violation::DetachedGraphicControlExtension)
+                }
+                _ => {}
+            }
+        }
+        match block {
+            Block::GraphicControlExtension(_) => pending_graphic_control_extension = Some(location),
+            Block::GraphicBlock { .. } | Block::TextBlock { .. } => {
+                pending_graphic_control_extension = None
+            }
+            _ => {}
+        }
+
+        let is_extension = match block {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                ..
+            } => graphic_control_extension.is_some(),
+            _ => true,
+        };
+        if is_extension && gif.version == GIFVersion::GIF87a {
+            diagnostics.report(location, Violation::ExtensionInGIF87a);
+        }
+
+        match block {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                image_descriptor,
+                local_color_table,
+                image_data,
+            } => {
+                let color_table = local_color_table.or(gif.global_color_table);
+
+                if let Some(graphic_control_extension) = graphic_control_extension {
+                    diagnostics.check_graphic_control_extension(
+                        location,
+                        graphic_control_extension,
+                        color_table,
+                    );
+                }
+                diagnostics.check_image_descriptor(location, image_descriptor, gif);
+                if color_table.is_none() {
+                    diagnostics.report(location, Violation::MissingColorTable);
+                }
+                if image_data.lzw_minimum_code_size < 2 || image_data.lzw_minimum_code_size > 8 {
+                    diagnostics.report(
+                        location,
+                        Violation::InvalidLZWMinimumCodeSize(image_data.lzw_minimum_code_size),
+                    );
+                }
+            }
+            Block::TextBlock {
+                graphic_control_extension: Some(graphic_control_extension),
+                ..
+            } => diagnostics.check_graphic_control_extension(
+                location,
+                graphic_control_extension,
+                gif.global_color_table,
+            ),
+            Block::GraphicControlExtension(graphic_control_extension) => diagnostics
+                .check_graphic_control_extension(
+                    location,
+                    graphic_control_extension,
+                    controlled_color_table(gif, index),
+                ),
+            _ => {}
+        }
+    }
+
+    if let Some(pending) = pending_graphic_control_extension {
+        diagnostics.report(pending, Violation::UnusedGraphicControlExtension);
+    }
+
+    if !gif.trailer {
+        diagnostics.report(Location::Trailer, Violation::MissingTrailer);
+    }
+
+    // superseded and detached Graphic Control Extensions are only found at a later block
+    diagnostics
+        .0
+        .sort_by_key(|diagnostic| match diagnostic.location {
+            Location::Block(index) => index,
+            Location::Trailer => usize::MAX,
+        });
+
+    diagnostics.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::model::{ImageData, SubBlocks};
+    use super::*;
+
+    fn graphic_block<'a>(
+        graphic_control_extension: Option<GraphicControlExtension>,
+        image_descriptor: ImageDescriptor,
+        lzw_minimum_code_size: u8,
+    ) -> Block<'a> {
+        Block::GraphicBlock {
+            graphic_control_extension,
+            image_descriptor,
+            local_color_table: None,
+            image_data: ImageData {
+                lzw_minimum_code_size,
+                data: SubBlocks(&[0x02, 0x44, 0x01, 0x00][..]),
+            },
+        }
+    }
+
+    fn gif<'a>(version: GIFVersion, data: Vec<Block<'a>>, trailer: bool) -> GIF<'a> {
+        GIF {
+            version,
+            width: 10,
+            height: 10,
+            packed_field: 0x80,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: Some(&[0, 0, 0, 255, 255, 255][..]),
+            data,
+            trailer,
+        }
+    }
+
+    const GRAPHIC_CONTROL_EXTENSION: GraphicControlExtension = GraphicControlExtension {
+        byte_size: 4,
+        packed_field: 0,
+        delay_time: 0,
+        transparent_color_index: 0,
+    };
+
+    const IMAGE_DESCRIPTOR: ImageDescriptor = ImageDescriptor {
+        left: 0,
+        top: 0,
+        width: 10,
+        height: 10,
+        packed_field: 0,
+    };
+
+    #[test]
+    fn should_accept_valid_gif() {
+        let gif = gif(
+            GIFVersion::GIF89a,
+            vec![graphic_block(
+                Some(GRAPHIC_CONTROL_EXTENSION),
+                IMAGE_DESCRIPTOR,
+                2,
+            )],
+            true,
+        );
+        assert_eq!(validate(&gif), vec![]);
+    }
+
+    #[test]
+    fn should_report_invalid_graphic_control_extension() {
+        let gif = gif(
+            GIFVersion::GIF89a,
+            vec![graphic_block(
+                Some(GraphicControlExtension {
+                    byte_size: 5,
+                    packed_field: 0b_1000_0001,
+                    delay_time: 0,
+                    transparent_color_index: 2,
+                }),
+                IMAGE_DESCRIPTOR,
+                2,
+            )],
+            true,
+        );
+        let violations: Vec<Violation> = validate(&gif).iter().map(|d| d.violation).collect();
+        assert_eq!(
+            violations,
+            vec![
+                Violation::InvalidGraphicControlExtensionSize(5),
+                Violation::ReservedBitsSet,
+                Violation::TransparentColorIndexOutOfRange {
+                    index: 2,
+                    color_table_size: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_check_transparent_color_of_stray_graphic_control_extensions() {
+        let transparent = |transparent_color_index| GraphicControlExtension {
+            packed_field: 0b_0000_0001,
+            transparent_color_index,
+            ..GRAPHIC_CONTROL_EXTENSION
+        };
+        let gif = gif(
+            GIFVersion::GIF89a,
+            vec![
+                // checked against the Global Color Table of the image after the comment
+                Block::GraphicControlExtension(transparent(2)),
+                Block::CommentExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+                graphic_block(None, IMAGE_DESCRIPTOR, 2),
+                // superseded, so that it controls no image
+                Block::GraphicControlExtension(transparent(3)),
+                Block::GraphicControlExtension(transparent(1)),
+                graphic_block(None, IMAGE_DESCRIPTOR, 2),
+            ],
+            true,
+        );
+        let diagnostics: Vec<_> = validate(&gif)
+            .iter()
+            .map(|diagnostic| (diagnostic.location, diagnostic.violation))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    Location::Block(0),
+                    Violation::TransparentColorIndexOutOfRange {
+                        index: 2,
+                        color_table_size: 2
+                    }
+                ),
+                (
+                    Location::Block(0),
+                    Violation::DetachedGraphicControlExtension
+                ),
+                (
+                    Location::Block(3),
+                    Violation::SupersededGraphicControlExtension
+                ),
+                (
+                    Location::Block(4),
+                    Violation::DetachedGraphicControlExtension
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_invalid_image() {
+        let mut gif = gif(
+            GIFVersion::GIF89a,
+            vec![graphic_block(
+                None,
+                ImageDescriptor {
+                    left: 5,
+                    top: 0,
+                    width: 10,
+                    height: 10,
+                    packed_field: 0b_0001_0000,
+                },
+                12,
+            )],
+            false,
+        );
+        gif.global_color_table = None;
+        assert_eq!(
+            validate(&gif),
+            vec![
+                Diagnostic {
+                    severity: Severity::Warning,
+                    location: Location::Block(0),
+                    violation: Violation::ReservedBitsSet,
+                },
+                Diagnostic {
+                    severity: Severity::Warning,
+                    location: Location::Block(0),
+                    violation: Violation::ImageOutsideLogicalScreen,
+                },
+                Diagnostic {
+                    severity: Severity::Error,
+                    location: Location::Block(0),
+                    violation: Violation::MissingColorTable,
+                },
+                Diagnostic {
+                    severity: Severity::Error,
+                    location: Location::Block(0),
+                    violation: Violation::InvalidLZWMinimumCodeSize(12),
+                },
+                Diagnostic {
+                    severity: Severity::Warning,
+                    location: Location::Trailer,
+                    violation: Violation::MissingTrailer,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_extensions_in_gif87a() {
+        let gif = gif(
+            GIFVersion::GIF87a,
+            vec![
+                Block::CommentExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+                graphic_block(None, IMAGE_DESCRIPTOR, 2),
+                graphic_block(Some(GRAPHIC_CONTROL_EXTENSION), IMAGE_DESCRIPTOR, 2),
+            ],
+            true,
+        );
+        let locations: Vec<Location> = validate(&gif)
+            .iter()
+            .filter(|d| d.violation == Violation::ExtensionInGIF87a)
+            .map(|d| d.location)
+            .collect();
+        assert_eq!(locations, vec![Location::Block(0), Location::Block(2)]);
+    }
+
+    #[test]
+    fn should_report_misplaced_graphic_control_extensions() {
+        let gif = gif(
+            GIFVersion::GIF89a,
+            vec![
+                Block::GraphicControlExtension(GRAPHIC_CONTROL_EXTENSION),
+                Block::GraphicControlExtension(GRAPHIC_CONTROL_EXTENSION),
+                Block::CommentExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+                graphic_block(None, IMAGE_DESCRIPTOR, 2),
+                Block::GraphicControlExtension(GRAPHIC_CONTROL_EXTENSION),
+                graphic_block(Some(GRAPHIC_CONTROL_EXTENSION), IMAGE_DESCRIPTOR, 2),
+                Block::GraphicControlExtension(GRAPHIC_CONTROL_EXTENSION),
+            ],
+            true,
+        );
+        assert_eq!(
+            validate(&gif)
+                .iter()
+                .map(|diagnostic| (diagnostic.location, diagnostic.violation))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Location::Block(0),
+                    Violation::SupersededGraphicControlExtension
+                ),
+                (
+                    Location::Block(1),
+                    Violation::DetachedGraphicControlExtension
+                ),
+                (
+                    Location::Block(4),
+                    Violation::SupersededGraphicControlExtension
+                ),
+                (Location::Block(6), Violation::UnusedGraphicControlExtension),
+            ]
+        );
+    }
+}
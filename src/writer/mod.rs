@@ -0,0 +1,290 @@
+use super::model::{
+    Block, GIFVersion, GraphicControlExtension, ImageData, ImageDescriptor, SubBlocks, GIF,
+};
+use std::io::{Result, Write};
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_header<W: Write>(writer: &mut W, gif: &GIF<'_>) -> Result<()> {
+    writer.write_all(b"GIF")?;
+    writer.write_all(match gif.required_version() {
+        GIFVersion::GIF87a => b"87a",
+        GIFVersion::GIF89a => b"89a",
+    })?;
+    write_u16(writer, gif.width)?;
+    write_u16(writer, gif.height)?;
+    writer.write_all(&[
+        gif.packed_field,
+        gif.background_color_index,
+        gif.pixel_aspect_ratio,
+    ])?;
+    if let Some(global_color_table) = gif.global_color_table {
+        writer.write_all(global_color_table)?;
+    }
+    Ok(())
+}
+
+fn write_graphic_control_extension<W: Write>(
+    writer: &mut W,
+    graphic_control_extension: &GraphicControlExtension,
+) -> Result<()> {
+    writer.write_all(&[
+        0x21,
+        0xf9,
+        graphic_control_extension.byte_size,
+        graphic_control_extension.packed_field,
+    ])?;
+    write_u16(writer, graphic_control_extension.delay_time)?;
+    writer.write_all(&[graphic_control_extension.transparent_color_index, 0x00])
+}
+
+fn write_image_descriptor<W: Write>(
+    writer: &mut W,
+    image_descriptor: &ImageDescriptor,
+) -> Result<()> {
+    writer.write_all(&[0x2c])?;
+    write_u16(writer, image_descriptor.left)?;
+    write_u16(writer, image_descriptor.top)?;
+    write_u16(writer, image_descriptor.width)?;
+    write_u16(writer, image_descriptor.height)?;
+    writer.write_all(&[image_descriptor.packed_field])
+}
+
+fn write_extension<W: Write>(writer: &mut W, label: u8, data: &SubBlocks<'_>) -> Result<()> {
+    writer.write_all(&[0x21, label])?;
+    writer.write_all(data.0)
+}
+
+fn write_block<W: Write>(writer: &mut W, block: &Block<'_>) -> Result<()> {
+    match block {
+        Block::GraphicBlock {
+            graphic_control_extension,
+            image_descriptor,
+            local_color_table,
+            image_data:
+                ImageData {
+                    lzw_minimum_code_size,
+                    data,
+                },
+        } => {
+            if let Some(graphic_control_extension) = graphic_control_extension {
+                write_graphic_control_extension(writer, graphic_control_extension)?;
+            }
+            write_image_descriptor(writer, image_descriptor)?;
+            if let Some(local_color_table) = local_color_table {
+                writer.write_all(local_color_table)?;
+            }
+            writer.write_all(&[*lzw_minimum_code_size])?;
+            writer.write_all(data.0)
+        }
+        Block::TextBlock {
+            graphic_control_extension,
+            text,
+        } => {
+            if let Some(graphic_control_extension) = graphic_control_extension {
+                write_graphic_control_extension(writer, graphic_control_extension)?;
+            }
+            write_extension(writer, 0x01, text)
+        }
+        Block::ApplicationExtension(data) => write_extension(writer, 0xff, data),
+        Block::CommentExtension(data) => write_extension(writer, 0xfe, data),
+        Block::GraphicControlExtension(graphic_control_extension) => {
+            write_graphic_control_extension(writer, graphic_control_extension)
+        }
+        Block::UnknownExtension { label, data } => write_extension(writer, *label, data),
+    }
+}
+
+impl<'a> GIF<'a> {
+    /// The number of bytes taken by the Header, Logical Screen Descriptor and Global Color Table,
+    /// which is also the offset of the first block.
+    pub fn header_len(&self) -> usize {
+        13 + self.global_color_table.map_or(0, <[u8]>::len)
+    }
+}
+
+impl<'a> Block<'a> {
+    /// The number of bytes this block takes when written.
+    pub fn encoded_len(&self) -> usize {
+        let graphic_control_extension_len =
+            |graphic_control_extension: &Option<GraphicControlExtension>| {
+                if graphic_control_extension.is_some() {
+                    8
+                } else {
+                    0
+                }
+            };
+
+        match self {
+            Block::GraphicBlock {
+                graphic_control_extension,
+                local_color_table,
+                image_data,
+                ..
+            } => {
+                graphic_control_extension_len(graphic_control_extension)
+                    + 10
+                    + local_color_table.map_or(0, <[u8]>::len)
+                    + 1
+                    + image_data.data.0.len()
+            }
+            Block::TextBlock {
+                graphic_control_extension,
+                text,
+            } => graphic_control_extension_len(graphic_control_extension) + 2 + text.0.len(),
+            Block::ApplicationExtension(data)
+            | Block::CommentExtension(data)
+            | Block::UnknownExtension { data, .. } => 2 + data.0.len(),
+            Block::GraphicControlExtension(_) => 8,
+        }
+    }
+}
+
+/// Serializes a GIF, choosing the version according to `GIF::required_version`.
+///
+/// The packed fields are written as is, so they must agree with the presence of the color tables.
+/// A trailer is always written, even if the GIF was parsed without one.
+pub fn write_gif<W: Write>(gif: &GIF<'_>, writer: &mut W) -> Result<()> {
+    write_header(writer, gif)?;
+    for block in &gif.data {
+        write_block(writer, block)?;
+    }
+    writer.write_all(&[0x3b])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gif<'a>(data: Vec<Block<'a>>) -> GIF<'a> {
+        GIF {
+            version: GIFVersion::GIF89a,
+            width: 1,
+            height: 1,
+            packed_field: 0x80,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: Some(&[0, 0, 0, 255, 255, 255][..]),
+            data,
+            trailer: true,
+        }
+    }
+
+    const IMAGE_DESCRIPTOR: ImageDescriptor = ImageDescriptor {
+        left: 0,
+        top: 0,
+        width: 1,
+        height: 1,
+        packed_field: 0,
+    };
+
+    const IMAGE_DATA: ImageData<'static> = ImageData {
+        lzw_minimum_code_size: 2,
+        data: SubBlocks(&[0x02, 0x44, 0x01, 0x00]),
+    };
+
+    #[test]
+    fn should_write_gif87a_without_extensions() {
+        let mut result = Vec::new();
+        write_gif(
+            &gif(vec![Block::GraphicBlock {
+                graphic_control_extension: None,
+                image_descriptor: IMAGE_DESCRIPTOR,
+                local_color_table: None,
+                image_data: IMAGE_DATA,
+            }]),
+            &mut result,
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            vec![
+                // header
+                0x47, 0x49, 0x46, 0x38, 0x37, 0x61, // "GIF87a"
+                // logical screen descriptor
+                0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, //
+                // global color table
+                0x00, 0x00, 0x00, 0xff, 0xff, 0xff, //
+                // image descriptor
+                0x2c, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, //
+                // image data
+                0x02, 0x02, 0x44, 0x01, 0x00, //
+                // trailer
+                0x3b,
+            ]
+        );
+    }
+
+    #[test]
+    fn should_write_gif89a_with_extensions() {
+        let mut result = Vec::new();
+        write_gif(
+            &gif(vec![
+                Block::CommentExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+                Block::GraphicBlock {
+                    graphic_control_extension: Some(GraphicControlExtension {
+                        byte_size: 4,
+                        packed_field: 0x01,
+                        delay_time: 0x0102,
+                        transparent_color_index: 1,
+                    }),
+                    image_descriptor: IMAGE_DESCRIPTOR,
+                    local_color_table: None,
+                    image_data: IMAGE_DATA,
+                },
+            ]),
+            &mut result,
+        )
+        .unwrap();
+        assert_eq!(&result[..6], b"GIF89a");
+        assert_eq!(
+            &result[19..],
+            &[
+                // comment extension
+                0x21, 0xfe, 0x01, 0x41, 0x00, //
+                // graphic control extension
+                0x21, 0xf9, 0x04, 0x01, 0x02, 0x01, 0x01, 0x00, //
+                // image descriptor
+                0x2c, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, //
+                // image data
+                0x02, 0x02, 0x44, 0x01, 0x00, //
+                // trailer
+                0x3b,
+            ][..]
+        );
+    }
+
+    #[test]
+    fn should_compute_encoded_lengths() {
+        let gif = gif(vec![
+            Block::CommentExtension(SubBlocks(&[0x01, 0x41, 0x00][..])),
+            Block::GraphicBlock {
+                graphic_control_extension: Some(GraphicControlExtension {
+                    byte_size: 4,
+                    packed_field: 0,
+                    delay_time: 0,
+                    transparent_color_index: 0,
+                }),
+                image_descriptor: IMAGE_DESCRIPTOR,
+                local_color_table: Some(&[0, 0, 0, 255, 255, 255][..]),
+                image_data: IMAGE_DATA,
+            },
+            Block::TextBlock {
+                graphic_control_extension: None,
+                text: SubBlocks(&[0x01, 0x41, 0x00][..]),
+            },
+        ]);
+
+        let mut header = Vec::new();
+        write_header(&mut header, &gif).unwrap();
+        assert_eq!(gif.header_len(), header.len());
+
+        for block in &gif.data {
+            let mut result = Vec::new();
+            write_block(&mut result, block).unwrap();
+            assert_eq!(block.encoded_len(), result.len());
+        }
+    }
+}
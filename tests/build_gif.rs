@@ -0,0 +1,22 @@
+#![cfg(feature = "serde")]
+
+extern crate gift;
+use gift::{parse_gif, GIFDescription, Renderer};
+
+#[test]
+fn should_build_gif_from_json() {
+    let description: GIFDescription =
+        serde_json::from_str(include_str!("../fixtures/sample_1.json")).unwrap();
+    let mut gif_data = Vec::new();
+    description.encode().unwrap().write(&mut gif_data).unwrap();
+
+    let built = parse_gif(&gif_data).unwrap();
+    let expected = parse_gif(include_bytes!("../fixtures/sample_1.gif")).unwrap();
+    assert_eq!(built.width, expected.width);
+    assert_eq!(built.global_color_table, expected.global_color_table);
+
+    let built_frames: Vec<_> = Renderer::new(&built).map(Result::unwrap).collect();
+    let expected_frames: Vec<_> = Renderer::new(&expected).map(Result::unwrap).collect();
+    assert_eq!(built_frames.len(), 1);
+    assert_eq!(built_frames[0].image, expected_frames[0].image);
+}
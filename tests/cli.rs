@@ -0,0 +1,263 @@
+use gift::{
+    parse_gif, write_gif, Block, DisposalMethod, GraphicControlExtension, Renderer, SubBlocks,
+};
+use std::process::{Command, Output};
+
+fn gift(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gift"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn info_should_describe_gif() {
+    let output = gift(&["info", "fixtures/GifSample.gif"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("version:                89a"));
+    assert!(stdout.contains("logical screen:         32x52"));
+    assert!(stdout.contains("global color table:     2 colors"));
+    assert!(stdout.contains("background color index: 255"));
+    assert!(stdout.contains("loop count:             none"));
+    assert!(stdout.contains("duration:               0.00 s (0.10 s in browsers)"));
+
+    let block = stdout.lines().last().unwrap();
+    let columns: Vec<&str> = block.split_whitespace().collect();
+    assert_eq!(
+        columns,
+        vec![
+            "0",
+            "image",
+            "19",
+            "141",
+            "32x52+0+0",
+            "0",
+            "none",
+            "2",
+            "-",
+            "120"
+        ]
+    );
+}
+
+#[test]
+fn should_fail_on_unknown_command() {
+    let output = gift(&["frobnicate"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Usage"));
+}
+
+#[test]
+fn extract_should_write_frames() {
+    let directory = std::env::temp_dir().join("gift-extract-test");
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let output = gift(&[
+        "extract",
+        "--raw",
+        "--ppm",
+        "fixtures/sample_1.gif",
+        directory.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let frame = std::fs::read(directory.join("frame-0000-0ms.ppm")).unwrap();
+    assert!(frame.starts_with(b"P6\n10 10\n255\n"));
+    assert_eq!(frame.len(), 13 + 10 * 10 * 3);
+    // the top-left pixel is red
+    assert_eq!(&frame[13..16], &[0xff, 0x00, 0x00]);
+    assert!(directory.join("raw-0000-0ms.ppm").exists());
+}
+
+#[test]
+fn extract_should_apply_stray_graphic_control_extensions_to_raw_images() {
+    let gif_path = std::env::temp_dir().join("gift-extract-stray-test.gif");
+    let directory = std::env::temp_dir().join("gift-extract-stray-test");
+    let _ = std::fs::remove_dir_all(&directory);
+
+    // the Graphic Control Extension makes the red top-left pixel transparent, with a comment
+    // between it and the image
+    let gif_data = std::fs::read("fixtures/sample_1.gif").unwrap();
+    let mut gif = parse_gif(&gif_data).unwrap();
+    let image = match gif.data.pop() {
+        Some(Block::GraphicBlock {
+            image_descriptor,
+            local_color_table,
+            image_data,
+            ..
+        }) => Block::GraphicBlock {
+            graphic_control_extension: None,
+            image_descriptor,
+            local_color_table,
+            image_data,
+        },
+        _ => unreachable!(),
+    };
+    gif.data.push(Block::GraphicControlExtension(
+        GraphicControlExtension::new(DisposalMethod::Unspecified, 10, Some(1)),
+    ));
+    gif.data
+        .push(Block::CommentExtension(SubBlocks(&[1, b'a', 0])));
+    gif.data.push(image);
+    let mut stray_gif_data = Vec::new();
+    write_gif(&gif, &mut stray_gif_data).unwrap();
+    std::fs::write(&gif_path, &stray_gif_data).unwrap();
+
+    let output = gift(&[
+        "extract",
+        "--raw",
+        gif_path.to_str().unwrap(),
+        directory.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let raw = std::fs::read(directory.join("raw-0000-100ms.pam")).unwrap();
+    let header_end = b"ENDHDR\n";
+    let pixels = raw
+        .windows(header_end.len())
+        .position(|window| window == header_end)
+        .unwrap()
+        + header_end.len();
+    assert_eq!(raw.len(), pixels + 10 * 10 * 4);
+    assert_eq!(raw[pixels + 3], 0);
+}
+
+#[test]
+fn dump_should_annotate_every_byte() {
+    let output = gift(&["dump", "fixtures/sample_1.gif"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines
+        .contains(&"00000003  38 39 61                                         version: \"89a\""));
+    assert!(lines
+        .contains(&"00000010  ff 00 00                                         color 1: #ff0000"));
+    assert!(lines.contains(
+        &"0000001d  00 00                                            delay time: 0 (1/100 s)"
+    ));
+    assert!(lines.contains(
+        &"0000002b  02                                               LZW minimum code size: 2"
+    ));
+    assert!(lines.contains(
+        &"0000002d  8c 2d 99 87 2a 1c dc 33 a0 02 75 ec 95 fa a8 de  LZW codes: clear 1 6 6 2 9 9 7 ... (35 codes)"
+    ));
+    assert!(lines.contains(
+        &"00000044                                                   decoded 100 of 100 pixels"
+    ));
+    assert_eq!(
+        lines.last(),
+        Some(&"00000044  3b                                               trailer")
+    );
+}
+
+#[test]
+fn dump_should_report_where_parsing_stops() {
+    let gif_path = std::env::temp_dir().join("gift-dump-test.gif");
+    let gif_data = std::fs::read("fixtures/sample_1.gif").unwrap();
+    std::fs::write(&gif_path, &gif_data[..0x30]).unwrap();
+
+    let output = gift(&["dump", gif_path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.contains(
+        &"00000019  Parsing Error: Image Data ends at byte 48, at least 19 more bytes needed"
+    ));
+    assert!(lines.contains(&"00000019  Unparsed Data"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn info_should_print_json() {
+    let output = gift(&["info", "--json", "fixtures/sample_1.gif"]);
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["height"], 10);
+    assert_eq!(
+        json["data"][0]["GraphicBlock"]["image_descriptor"]["left"],
+        0
+    );
+}
+
+#[test]
+fn optimize_should_write_smaller_gif() {
+    let output_path = std::env::temp_dir().join("gift-optimize-test.gif");
+    let output = gift(&[
+        "optimize",
+        "--strip-comments",
+        "fixtures/giflib-logo.gif",
+        output_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let optimized = std::fs::read(&output_path).unwrap();
+    let original = std::fs::read("fixtures/giflib-logo.gif").unwrap();
+    assert!(optimized.len() <= original.len());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!("{} -> {} bytes", original.len(), optimized.len())));
+}
+
+#[test]
+fn concat_should_join_gifs() {
+    let output_path = std::env::temp_dir().join("gift-concat-test.gif");
+    let output = gift(&[
+        "concat",
+        "fixtures/GifSample.gif",
+        "fixtures/giflib-logo.gif",
+        output_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let concatenated = std::fs::read(&output_path).unwrap();
+    let gif = parse_gif(&concatenated).unwrap();
+    let sample = std::fs::read("fixtures/GifSample.gif").unwrap();
+    let logo = std::fs::read("fixtures/giflib-logo.gif").unwrap();
+    let (sample, logo) = (parse_gif(&sample).unwrap(), parse_gif(&logo).unwrap());
+    assert_eq!(gif.width, sample.width.max(logo.width));
+    assert_eq!(gif.height, sample.height.max(logo.height));
+    assert_eq!(
+        Renderer::new(&gif).count(),
+        Renderer::new(&sample).count() + Renderer::new(&logo).count()
+    );
+}
+
+#[test]
+fn should_only_write_complete_gifs() {
+    let gif_path = std::env::temp_dir().join("gift-truncated-test.gif");
+    let output_path = std::env::temp_dir().join("gift-truncated-test-optimized.gif");
+    let gif_data = std::fs::read("fixtures/sample_1.gif").unwrap();
+    std::fs::write(&gif_path, &gif_data[..0x30]).unwrap();
+    let _ = std::fs::remove_file(&output_path);
+
+    let output = gift(&["info", gif_path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains(
+        "only up to where parsing stopped: Image Data ends at byte 48, at least 19 more bytes needed"
+    ));
+
+    let output = gift(&[
+        "optimize",
+        gif_path.to_str().unwrap(),
+        output_path.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Image Data ends at byte 48"));
+    assert!(!output_path.exists());
+
+    let output = gift(&[
+        "concat",
+        "fixtures/sample_1.gif",
+        gif_path.to_str().unwrap(),
+        output_path.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(!output_path.exists());
+}
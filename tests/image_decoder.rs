@@ -0,0 +1,35 @@
+#![cfg(feature = "image")]
+extern crate gift;
+use gift::{parse_gif, GIFDecoder, Renderer};
+use image::{AnimationDecoder, DynamicImage};
+
+#[test]
+fn should_decode_first_frame_as_image() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    let image = DynamicImage::from_decoder(GIFDecoder::new(gif_data).unwrap()).unwrap();
+
+    let gif = parse_gif(gif_data).unwrap();
+    let first_frame = Renderer::new(&gif).next().unwrap().unwrap();
+    assert_eq!(image.into_rgba8().into_raw(), first_frame.image.pixels);
+}
+
+#[test]
+fn should_decode_frames_with_delays() {
+    let gif_data = include_bytes!("../fixtures/GifSample.gif");
+    let frames = GIFDecoder::new(gif_data)
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+
+    let gif = parse_gif(gif_data).unwrap();
+    let rendered_frames = Renderer::new(&gif).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(frames.len(), rendered_frames.len());
+    for (frame, rendered_frame) in frames.iter().zip(&rendered_frames) {
+        assert_eq!(
+            frame.delay().numer_denom_ms(),
+            (u32::from(rendered_frame.delay_time) * 10, 1)
+        );
+        assert_eq!(frame.buffer().as_raw(), &rendered_frame.image.pixels);
+    }
+}
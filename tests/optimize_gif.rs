@@ -0,0 +1,33 @@
+extern crate gift;
+
+use gift::{parse_gif, GIFOptimizer, Renderer, GIF};
+
+fn render(gif: &GIF<'_>) -> Vec<(u16, Vec<u8>)> {
+    Renderer::new(gif)
+        .map(|frame| {
+            let frame = frame.unwrap();
+            (frame.delay_time, frame.image.pixels)
+        })
+        .collect()
+}
+
+#[test]
+fn should_optimize_without_changing_rendering() {
+    let fixtures: [&[u8]; 3] = [
+        include_bytes!("../fixtures/sample_1.gif"),
+        include_bytes!("../fixtures/GifSample.gif"),
+        include_bytes!("../fixtures/giflib-logo.gif"),
+    ];
+    for gif_data in fixtures.iter() {
+        let gif = parse_gif(gif_data).unwrap();
+        let mut optimized_data = Vec::new();
+        GIFOptimizer::new()
+            .optimize(&gif)
+            .write(&mut optimized_data)
+            .unwrap();
+
+        assert!(optimized_data.len() <= gif_data.len());
+        let optimized = parse_gif(&optimized_data).unwrap();
+        assert_eq!(render(&optimized), render(&gif));
+    }
+}
@@ -1,5 +1,5 @@
 extern crate gift;
-use gift::{parse_gif, GIFVersion, GIF, Block, GraphicControlExtension, ImageDescriptor, SubBlocks, ImageData};
+use gift::{parse_block, parse_gif, parse_gif_with_ranges, parse_header, ParsedGIF, ParsingError, ParsingErrorKind, validate, write_gif, Location, Violation, GIFVersion, GIF, Block, GraphicControlExtension, ImageDescriptor, SubBlocks, ImageData};
 
 #[test]
 fn should_parse_sample_1_gif() {
@@ -10,6 +10,9 @@ fn should_parse_sample_1_gif() {
             version: GIFVersion::GIF89a,
             width: 10,
             height: 10,
+            packed_field: 0x91,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
             global_color_table: Some(
                 &[0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00][..]
             ),
@@ -38,6 +41,7 @@ fn should_parse_sample_1_gif() {
                     )
                 },
             }],
+            trailer: true,
         })
     );
 }
@@ -51,6 +55,9 @@ fn should_parse_giflib_logo_gif() {
             version: GIFVersion::GIF89a,
             width: 50,
             height: 50,
+            packed_field: 0xf6,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
             global_color_table: Some(
                 &[
                     0xBA, 0x0D, 0x03, 0xBD, 0x0C, 0x03, 0xBA, 0x0F, 0x05, 0xAD, 0x0C, 0x13, 0xA9,
@@ -178,6 +185,222 @@ fn should_parse_giflib_logo_gif() {
                     )
                 },
             }],
+            trailer: true,
         })
     );
 }
+
+#[test]
+fn should_preserve_unknown_extensions() {
+    let extension = [0x21, 0x99, 0x03, 0x01, 0x02, 0x03, 0x00];
+    let mut gif_data = include_bytes!("../fixtures/sample_1.gif").to_vec();
+    // insert the extension right after the global color table
+    gif_data.splice(0x19..0x19, extension.iter().cloned());
+
+    let gif = parse_gif(&gif_data).unwrap();
+    assert_eq!(gif.data.len(), 2);
+    assert_eq!(
+        gif.data[0],
+        Block::UnknownExtension {
+            label: 0x99,
+            data: SubBlocks(&extension[2..])
+        }
+    );
+    match gif.data[1] {
+        Block::GraphicBlock { .. } => {}
+        _ => panic!("expected a graphic block"),
+    }
+}
+
+#[test]
+fn should_preserve_stray_graphic_control_extensions() {
+    let comment = [0x21, 0xfe, 0x01, 0x61, 0x00];
+    let mut gif_data = include_bytes!("../fixtures/sample_1.gif").to_vec();
+    // insert the comment between the graphic control extension and the image descriptor
+    gif_data.splice(0x21..0x21, comment.iter().cloned());
+
+    let gif = parse_gif(&gif_data).unwrap();
+    assert_eq!(gif.data.len(), 3);
+    assert_eq!(
+        gif.data[0],
+        Block::GraphicControlExtension(GraphicControlExtension {
+            byte_size: 4,
+            packed_field: 0,
+            delay_time: 0,
+            transparent_color_index: 0
+        })
+    );
+    assert_eq!(gif.data[1], Block::CommentExtension(SubBlocks(&comment[2..])));
+    match gif.data[2] {
+        Block::GraphicBlock {
+            graphic_control_extension: None,
+            ..
+        } => {}
+        _ => panic!("expected a graphic block without graphic control extension"),
+    }
+    assert!(gif.trailer);
+
+    let mut written = Vec::new();
+    write_gif(&gif, &mut written).unwrap();
+    assert_eq!(written, gif_data);
+}
+
+#[test]
+fn should_apply_last_of_consecutive_graphic_control_extensions() {
+    let extension = [0x21, 0xf9, 0x04, 0x00, 0x32, 0x00, 0x00, 0x00];
+    let mut gif_data = include_bytes!("../fixtures/sample_1.gif").to_vec();
+    // insert a graphic control extension with a delay of 50 before the one of the image
+    gif_data.splice(0x19..0x19, extension.iter().cloned());
+
+    let gif = parse_gif(&gif_data).unwrap();
+    assert_eq!(gif.data.len(), 2);
+    let frames: Vec<_> = gif.frames().collect();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].delay_time(), 0);
+    assert_eq!(
+        validate(&gif)
+            .iter()
+            .map(|diagnostic| (diagnostic.location, diagnostic.violation))
+            .collect::<Vec<_>>(),
+        vec![(
+            Location::Block(0),
+            Violation::SupersededGraphicControlExtension
+        )]
+    );
+}
+
+#[test]
+fn should_record_block_ranges() {
+    for gif_data in &[
+        &include_bytes!("../fixtures/sample_1.gif")[..],
+        &include_bytes!("../fixtures/giflib-logo.gif")[..],
+        &include_bytes!("../fixtures/GifSample.gif")[..],
+    ] {
+        let ParsedGIF { gif, ranges, error } = parse_gif_with_ranges(gif_data).unwrap();
+        assert_eq!(error, None);
+        assert_eq!(ranges.len(), gif.data.len());
+        let mut offset = gif.header_len();
+        for (block, range) in gif.data.iter().zip(&ranges) {
+            assert_eq!(range.start, offset);
+            let mut written = Vec::new();
+            write_gif(
+                &GIF {
+                    data: vec![*block],
+                    ..gif.clone()
+                },
+                &mut written,
+            )
+            .unwrap();
+            assert_eq!(
+                &gif_data[range.clone()],
+                &written[gif.header_len()..written.len() - 1]
+            );
+            offset = range.end;
+        }
+    }
+}
+
+#[test]
+fn should_parse_gif_without_trailer() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    let parsed = parse_gif_with_ranges(&gif_data[..gif_data.len() - 1]).unwrap();
+    assert_eq!(parsed.gif.data.len(), 1);
+    assert!(!parsed.gif.trailer);
+    assert_eq!(
+        parsed.error,
+        Some(ParsingError {
+            offset: 0x44,
+            context: "block",
+            kind: ParsingErrorKind::Incomplete(1),
+        })
+    );
+}
+
+#[test]
+fn should_report_truncated_block() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    // cut within the first sub-block of the image data, whose 22 bytes start at 0x2d
+    let parsed = parse_gif_with_ranges(&gif_data[..0x30]).unwrap();
+    assert!(parsed.gif.data.is_empty());
+    assert!(!parsed.gif.trailer);
+    assert_eq!(
+        parsed.error,
+        Some(ParsingError {
+            offset: 0x30,
+            context: "Image Data",
+            kind: ParsingErrorKind::Incomplete(19),
+        })
+    );
+    assert_eq!(parse_gif(&gif_data[..0x30]).unwrap().data, vec![]);
+
+    // an invalid block after the image
+    let mut gif_data = gif_data.to_vec();
+    gif_data.insert(0x44, 0x00);
+    let error = parse_gif_with_ranges(&gif_data).unwrap().error.unwrap();
+    assert_eq!(error.to_string(), "invalid block at byte 68");
+}
+
+#[test]
+fn should_read_loop_count() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    assert_eq!(parse_gif(gif_data).unwrap().loop_count(), None);
+
+    let extension = [
+        0x21, 0xFF, 0x0B, 0x4E, 0x45, 0x54, 0x53, 0x43, 0x41, 0x50, 0x45, 0x32, 0x2E, 0x30, 0x03,
+        0x01, 0x05, 0x00, 0x00,
+    ];
+    let mut gif_data = gif_data.to_vec();
+    gif_data.splice(0x19..0x19, extension.iter().cloned());
+    assert_eq!(parse_gif(&gif_data).unwrap().loop_count(), Some(5));
+}
+
+#[test]
+fn should_parse_gif_as_data_arrives() {
+    let gif_data = include_bytes!("../fixtures/GifSample.gif");
+    let expected = parse_gif(gif_data).unwrap();
+
+    // a few bytes arrive at a time, parsing again whenever the data is incomplete
+    let mut available = 0;
+    let mut arrive = || {
+        available = (available + 7).min(gif_data.len());
+        &gif_data[..available]
+    };
+    let (mut gif, mut offset) = loop {
+        match parse_header(arrive()) {
+            Ok(parsed) => break parsed,
+            Err(err) => assert!(err.is_incomplete()),
+        }
+    };
+    let mut data = arrive();
+    while data.get(offset) != Some(&0x3b) {
+        match parse_block(&data[offset..]) {
+            Ok((block, len)) => {
+                gif.data.push(block);
+                offset += len;
+            }
+            Err(err) => {
+                assert!(err.is_incomplete());
+                data = arrive();
+            }
+        }
+    }
+    gif.trailer = true;
+    assert_eq!(gif, expected);
+}
+
+#[test]
+fn should_report_where_parsing_fails() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    assert_eq!(
+        parse_gif(&gif_data[..10]),
+        Err(ParsingError {
+            offset: 10,
+            context: "Logical Screen Descriptor",
+            kind: ParsingErrorKind::Incomplete(1),
+        })
+    );
+    assert_eq!(
+        parse_gif(b"GIF90a").unwrap_err().to_string(),
+        "invalid version at byte 3"
+    );
+}
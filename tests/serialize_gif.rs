@@ -0,0 +1,76 @@
+#![cfg(feature = "serde")]
+
+extern crate gift;
+use gift::{
+    parse_gif, Block, DeserializedBlock, DeserializedGIF, GraphicControlExtension, ImageDescriptor,
+    SubBlocks,
+};
+
+#[test]
+fn should_serialize_gif_as_json() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    let gif = parse_gif(gif_data).unwrap();
+
+    let json: serde_json::Value = serde_json::to_value(&gif).unwrap();
+    assert_eq!(json["version"], "GIF89a");
+    assert_eq!(json["width"], 10);
+    assert_eq!(json["global_color_table"], "/////wAAAAD/AAAA");
+    assert_eq!(json["trailer"], true);
+
+    let graphic_block = &json["data"][0]["GraphicBlock"];
+    assert_eq!(graphic_block["local_color_table"], serde_json::Value::Null);
+    assert_eq!(graphic_block["image_descriptor"]["width"], 10);
+    assert_eq!(graphic_block["graphic_control_extension"]["byte_size"], 4);
+    assert_eq!(graphic_block["image_data"]["lzw_minimum_code_size"], 2);
+    assert_eq!(
+        graphic_block["image_data"]["data"],
+        "FowtmYcqHNwzoAJ17JX6qN5gjASRTAEA"
+    );
+}
+
+#[test]
+fn should_deserialize_owned_structures() {
+    let graphic_control_extension: GraphicControlExtension = serde_json::from_str(
+        r#"{"byte_size": 4, "packed_field": 9, "delay_time": 10, "transparent_color_index": 3}"#,
+    )
+    .unwrap();
+    assert_eq!(graphic_control_extension.delay_time, 10);
+
+    let image_descriptor: ImageDescriptor = serde_json::from_str(
+        r#"{"left": 1, "top": 2, "width": 3, "height": 4, "packed_field": 0}"#,
+    )
+    .unwrap();
+    assert_eq!(image_descriptor.height, 4);
+}
+
+#[test]
+fn should_deserialize_gifs_from_json() {
+    for gif_data in &[
+        &include_bytes!("../fixtures/sample_1.gif")[..],
+        &include_bytes!("../fixtures/giflib-logo.gif")[..],
+        &include_bytes!("../fixtures/GifSample.gif")[..],
+    ] {
+        let gif = parse_gif(gif_data).unwrap();
+        let json = serde_json::to_string(&gif).unwrap();
+        let deserialized: DeserializedGIF = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.gif(), gif);
+    }
+}
+
+#[test]
+fn should_deserialize_blocks() {
+    let comment = [5, b'h', b'e', b'l', b'l', b'o', 0];
+    let block = Block::CommentExtension(SubBlocks(&comment));
+    let json = serde_json::to_value(block).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "CommentExtension": "BWhlbGxvAA==" })
+    );
+    let deserialized: DeserializedBlock = serde_json::from_value(json).unwrap();
+    assert_eq!(deserialized.block(), block);
+
+    assert!(
+        serde_json::from_str::<DeserializedBlock>(r#"{"CommentExtension": "not base64!"}"#)
+            .is_err()
+    );
+}
@@ -0,0 +1,30 @@
+extern crate gift;
+use gift::{downgrade_to_gif87a, parse_gif, write_gif, GIFVersion};
+
+#[test]
+fn should_write_parsed_gifs_unchanged() {
+    for gif_data in &[
+        &include_bytes!("../fixtures/sample_1.gif")[..],
+        &include_bytes!("../fixtures/giflib-logo.gif")[..],
+    ] {
+        let gif = parse_gif(gif_data).unwrap();
+        let mut result = Vec::new();
+        write_gif(&gif, &mut result).unwrap();
+        assert_eq!(&result[..], *gif_data);
+    }
+}
+
+#[test]
+fn should_write_downgraded_gif_as_gif87a() {
+    let gif_data = include_bytes!("../fixtures/sample_1.gif");
+    let (gif, stripped) = downgrade_to_gif87a(&parse_gif(gif_data).unwrap());
+    assert_eq!(stripped.len(), 1);
+
+    let mut result = Vec::new();
+    write_gif(&gif, &mut result).unwrap();
+    assert_eq!(&result[..6], b"GIF87a");
+
+    let reparsed = parse_gif(&result).unwrap();
+    assert_eq!(reparsed.version, GIFVersion::GIF87a);
+    assert_eq!(reparsed, gif);
+}